@@ -0,0 +1,72 @@
+use std::{
+    env,
+    fs,
+    path::PathBuf,
+    process
+};
+use chip_8_emu::asm::{
+    self,
+    Assembly
+};
+
+const USAGE: &str = "usage: chip8-asm <source> [-o <rom>] [-s <symbols>]";
+
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+
+fn main() {
+    let mut source_path: Option<PathBuf> = None;
+    let mut rom_path: Option<PathBuf> = None;
+    let mut symbols_path: Option<PathBuf> = None;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => rom_path = Some(args.next().unwrap_or_else(|| usage_error()).into()),
+            "-s" => symbols_path = Some(args.next().unwrap_or_else(|| usage_error()).into()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if source_path.is_none() && !arg.starts_with('-') => source_path = Some(arg.into()),
+            _ => usage_error()
+        }
+    }
+
+    let Some(source_path) = source_path else {
+        usage_error();
+    };
+
+    let rom_path: PathBuf = rom_path.unwrap_or_else(|| source_path.with_extension("ch8"));
+    let symbols_path: PathBuf = symbols_path.unwrap_or_else(|| rom_path.with_extension("sym"));
+
+    let source: String = match fs::read_to_string(&source_path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", source_path.display(), err);
+            process::exit(1);
+        }
+    };
+
+    let assembly: Assembly = match asm::assemble(&source) {
+        Ok(assembly) => assembly,
+        Err(err) => {
+            eprintln!("{}:{}", source_path.display(), err);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = fs::write(&rom_path, &assembly.rom) {
+        eprintln!("failed to write {}: {}", rom_path.display(), err);
+        process::exit(1);
+    }
+
+    if let Err(err) = fs::write(&symbols_path, assembly.symbols.to_string()) {
+        eprintln!("failed to write {}: {}", symbols_path.display(), err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    env,
+    fs,
+    io,
+    path::PathBuf,
+    process
+};
+use chip_8_emu::{
+    Machine,
+    asm::SymbolMap,
+    config::Settings,
+    debugger::repl
+};
+
+const USAGE: &str = "usage: chip8-debug [options] <rom>
+
+options:
+  -s, --symbols <file>      label addresses, <rom>.sym by default
+  -p, --platform <name>     vip, chip48, schip or xochip (default vip)
+  -q, --quirk <name=value>  override one quirk of the platform, e.g. wrap=1";
+
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+
+fn fail(message: String) -> ! {
+    eprintln!("chip8-debug: {}", message);
+    process::exit(1);
+}
+
+
+fn main() {
+    let mut rom_path: Option<PathBuf> = None;
+    let mut symbols_path: Option<PathBuf> = None;
+    let mut settings: Settings = Settings::default();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage_error());
+
+        match arg.as_str() {
+            "-s" | "--symbols" => symbols_path = Some(value().into()),
+            "-p" | "--platform" => settings.platform = Some(value().parse().unwrap_or_else(|message| fail(message))),
+            "-q" | "--quirk" => {
+                let quirk: String = value();
+                let Some((name, value)) = quirk.split_once('=') else {
+                    fail(format!("quirk '{}' must be name=value", quirk));
+                };
+
+                settings.quirks.push((name.to_string(), value.to_string()));
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if rom_path.is_none() && !arg.starts_with('-') => rom_path = Some(arg.into()),
+            _ => usage_error()
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        usage_error();
+    };
+
+    // symbols written by chip8-asm next to the rom are picked up automatically
+    let symbols: SymbolMap = fs::read_to_string(symbols_path.unwrap_or_else(|| rom_path.with_extension("sym")))
+        .map(|text| SymbolMap::parse(&text))
+        .unwrap_or_default();
+
+    let mut machine: Machine = settings.machine().unwrap_or_else(|message| fail(message));
+
+    if let Err(err) = machine.init(rom_path.to_string_lossy().into_owned()) {
+        fail(format!("failed to load rom: {}", err));
+    }
+
+    if let Err(err) = repl::run(&mut machine, &symbols, io::stdin().lock(), io::stdout()) {
+        fail(err.to_string());
+    }
+}
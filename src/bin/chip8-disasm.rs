@@ -0,0 +1,47 @@
+use std::{
+    env,
+    fs,
+    process
+};
+use chip_8_emu::{
+    cpu::instruction::Syntax,
+    disasm::Listing
+};
+
+const USAGE: &str = "usage: chip8-disasm [--classic] <rom>";
+
+
+fn main() {
+    let mut syntax: Syntax = Syntax::Octo;
+    let mut rom_path: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--classic" => syntax = Syntax::Classic,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if rom_path.is_none() && !arg.starts_with('-') => rom_path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+
+    let rom: Vec<u8> = match fs::read(&rom_path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", rom_path, err);
+            process::exit(1);
+        }
+    };
+
+    print!("{}", Listing::trace(&rom).with_syntax(syntax));
+}
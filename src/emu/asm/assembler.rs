@@ -0,0 +1,692 @@
+use std::collections::{
+    HashMap,
+    VecDeque
+};
+use super::{
+    Assembly,
+    AsmError,
+    SymbolMap,
+    lexer::Token
+};
+use super::super::cpu::ENTRY_POINT;
+
+const MAX_EXPANSIONS: usize = 10000;   // guards against recursive macros
+
+
+enum Fixup {
+    Address(usize),     // low 12 bits of the instruction at this rom offset
+    Long(usize)         // 16 bit word at this rom offset
+}
+
+
+// open `if ... begin` or `loop` waiting for its closing keyword
+enum Block {
+    If { jump: usize },                 // jump over the branch, patched by `else` or `end`
+    Loop { start: u16, breaks: Vec<usize> }
+}
+
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>
+}
+
+
+pub struct Assembler {
+    tokens: VecDeque<Token>,
+    last: Token,                        // position for errors at end of input
+    rom: Vec<u8>,
+    pos: usize,                         // write offset from the entry point
+    labels: HashMap<String, u16>,
+    consts: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(Token, Fixup)>,
+    blocks: Vec<(Token, Block)>,
+    expansions: usize
+}
+
+
+impl Assembler {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        let last: Token = tokens.last().cloned().unwrap_or(Token {
+            text: String::new(),
+            line: 1,
+            column: 1
+        });
+
+        Self {
+            tokens: VecDeque::from(tokens),
+            last,
+            rom: Vec::new(),
+            pos: 0,
+            labels: HashMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            expansions: 0
+        }
+    }
+
+    pub fn run(mut self) -> Result<Assembly, AsmError> {
+        while let Some(token) = self.tokens.pop_front() {
+            self.statement(token)?;
+        }
+
+        if let Some((token, block)) = self.blocks.pop() {
+            let keyword: &str = match block {
+                Block::If { .. } => "end",
+                Block::Loop { .. } => "again"
+            };
+
+            return Err(Self::error(&token, format!("missing '{}'", keyword)));
+        }
+
+        for (token, fixup) in std::mem::take(&mut self.fixups) {
+            let addr: u16 = *self.labels
+                .get(&token.text)
+                .ok_or_else(|| Self::error(&token, format!("undefined label '{}'", token.text)))?;
+
+            match fixup {
+                Fixup::Address(offset) => {
+                    if addr > 0xFFF {
+                        return Err(Self::error(&token, format!("label '{}' is out of 12 bit range", token.text)));
+                    }
+
+                    self.rom[offset] |= (addr >> 8) as u8;
+                    self.rom[offset + 1] = addr as u8;
+                }
+                Fixup::Long(offset) => {
+                    self.rom[offset] = (addr >> 8) as u8;
+                    self.rom[offset + 1] = addr as u8;
+                }
+            }
+        }
+
+        let mut symbols: SymbolMap = SymbolMap::new();
+
+        for (name, addr) in self.labels {
+            symbols.insert(name, addr);
+        }
+
+        Ok(Assembly {
+            rom: self.rom,
+            symbols
+        })
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AsmError> {
+        match token.text.as_str() {
+            ":" => {
+                let name: Token = self.next()?;
+
+                self.check_name(&name)?;
+
+                if self.labels.contains_key(&name.text) {
+                    return Err(Self::error(&name, format!("label '{}' is already defined", name.text)));
+                }
+
+                let addr: u16 = self.here(&name)?;
+                self.labels.insert(name.text, addr);
+            }
+            ":const" => {
+                let name: Token = self.next()?;
+                self.check_name(&name)?;
+
+                let value_token: Token = self.next()?;
+                let value: i64 = self.value(&value_token)?;
+
+                self.consts.insert(name.text, value);
+            }
+            ":alias" => {
+                let name: Token = self.next()?;
+                self.check_name(&name)?;
+
+                let register: u8 = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":macro" => self.define_macro()?,
+            ":org" => {
+                let addr_token: Token = self.next()?;
+                let addr: i64 = self.value(&addr_token)?;
+
+                if !(ENTRY_POINT as i64..=0xFFFF).contains(&addr) {
+                    return Err(Self::error(&addr_token, "origin must be between 0x200 and 0xFFFF"));
+                }
+
+                self.pos = addr as usize - ENTRY_POINT as usize;
+            }
+            ":call" => self.emit_address(0x2000)?,
+            ":byte" => {
+                let value: u8 = self.byte()?;
+                self.emit_byte(&token, value)?;
+            }
+            "clear" => self.emit(&token, 0x00E0)?,
+            "return" | ";" => self.emit(&token, 0x00EE)?,
+            "scroll-down" => {
+                let n: u16 = self.nibble()?;
+                self.emit(&token, 0x00C0 | n)?;
+            }
+            "scroll-up" => {
+                let n: u16 = self.nibble()?;
+                self.emit(&token, 0x00D0 | n)?;
+            }
+            "scroll-right" => self.emit(&token, 0x00FB)?,
+            "scroll-left" => self.emit(&token, 0x00FC)?,
+            "exit" => self.emit(&token, 0x00FD)?,
+            "lores" => self.emit(&token, 0x00FE)?,
+            "hires" => self.emit(&token, 0x00FF)?,
+            "jump" => self.emit_address(0x1000)?,
+            "jump0" => self.emit_address(0xB000)?,
+            "sprite" => {
+                let x: u16 = self.register()? as u16;
+                let y: u16 = self.register()? as u16;
+                let n: u16 = self.nibble()?;
+
+                self.emit(&token, 0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "plane" => {
+                let n: u16 = self.nibble()?;
+
+                if n > 3 {
+                    return Err(Self::error(&token, "plane must be between 0 and 3"));
+                }
+
+                self.emit(&token, 0xF001 | n << 8)?;
+            }
+            "audio" => self.emit(&token, 0xF002)?,
+            "bcd" => self.emit_fx(&token, 0x33)?,
+            "saveflags" => self.emit_fx(&token, 0x75)?,
+            "loadflags" => self.emit_fx(&token, 0x85)?,
+            "save" => self.emit_range(&token, 0x55, 0x5002)?,
+            "load" => self.emit_range(&token, 0x65, 0x5003)?,
+            "delay" => {
+                self.expect(":=")?;
+                self.emit_fx(&token, 0x15)?;
+            }
+            "buzzer" => {
+                self.expect(":=")?;
+                self.emit_fx(&token, 0x18)?;
+            }
+            "pitch" => {
+                self.expect(":=")?;
+                self.emit_fx(&token, 0x3A)?;
+            }
+            "i" => self.index(&token)?,
+            "if" => self.conditional(&token)?,
+            "else" => {
+                let jump: usize = match self.blocks.pop() {
+                    Some((_, Block::If { jump })) => jump,
+                    _ => return Err(Self::error(&token, "'else' without 'if ... begin'"))
+                };
+
+                let end_jump: usize = self.pos;
+
+                self.emit(&token, 0x1000)?;
+                self.patch_jump(jump, &token)?;
+                self.blocks.push((token, Block::If { jump: end_jump }));
+            }
+            "end" => match self.blocks.pop() {
+                Some((_, Block::If { jump })) => self.patch_jump(jump, &token)?,
+                _ => return Err(Self::error(&token, "'end' without 'if ... begin'"))
+            },
+            "loop" => {
+                let start: u16 = self.here(&token)?;
+                self.blocks.push((token, Block::Loop { start, breaks: Vec::new() }));
+            }
+            "while" => {
+                let skip: u16 = Self::complement(self.condition()?);
+                let jump: usize = self.pos + 2;
+
+                self.emit(&token, skip)?;
+                self.emit(&token, 0x1000)?;
+
+                match self.blocks.iter_mut().rev().find(|(_, block)| matches!(block, Block::Loop { .. })) {
+                    Some((_, Block::Loop { breaks, .. })) => breaks.push(jump),
+                    _ => return Err(Self::error(&token, "'while' outside of 'loop'"))
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some((_, Block::Loop { start, breaks })) => {
+                    if start > 0xFFF {
+                        return Err(Self::error(&token, "block ends outside of 12 bit range"));
+                    }
+
+                    self.emit(&token, 0x1000 | start)?;
+
+                    for jump in breaks {
+                        self.patch_jump(jump, &token)?;
+                    }
+                }
+                _ => return Err(Self::error(&token, "'again' without 'loop'"))
+            },
+            _ if self.is_register(&token.text) => self.register_statement(token)?,
+            _ if self.macros.contains_key(&token.text) => self.expand_macro(&token)?,
+            _ if Self::number(&token.text).is_some() || self.consts.contains_key(&token.text) => {
+                let value: u8 = self.byte_value(&token)?;
+                self.emit_byte(&token, value)?;
+            }
+            _ => {
+                // a bare label name calls it
+                self.check_name(&token)?;
+                self.tokens.push_front(token);
+                self.emit_address(0x2000)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn register_statement(&mut self, token: Token) -> Result<(), AsmError> {
+        let x: u16 = self.register_value(&token)? as u16;
+        let op: Token = self.next()?;
+        let rhs: Token = self.next()?;
+        let rhs_register: Option<u16> = self.is_register(&rhs.text)
+            .then(|| self.register_value(&rhs))
+            .transpose()?
+            .map(|y| y as u16);
+
+        let opcode: u16 = match (op.text.as_str(), rhs_register) {
+            (":=", Some(y)) => 0x8000 | x << 8 | y << 4,
+            (":=", None) => match rhs.text.as_str() {
+                "random" => 0xC000 | x << 8 | self.byte()? as u16,
+                "key" => 0xF00A | x << 8,
+                "delay" => 0xF007 | x << 8,
+                _ => 0x6000 | x << 8 | self.byte_value(&rhs)? as u16
+            },
+            ("+=", Some(y)) => 0x8004 | x << 8 | y << 4,
+            ("+=", None) => 0x7000 | x << 8 | self.byte_value(&rhs)? as u16,
+            ("-=", Some(y)) => 0x8005 | x << 8 | y << 4,
+            ("-=", None) => 0x7000 | x << 8 | self.byte_value(&rhs)?.wrapping_neg() as u16,
+            ("|=", Some(y)) => 0x8001 | x << 8 | y << 4,
+            ("&=", Some(y)) => 0x8002 | x << 8 | y << 4,
+            ("^=", Some(y)) => 0x8003 | x << 8 | y << 4,
+            (">>=", Some(y)) => 0x8006 | x << 8 | y << 4,
+            ("=-", Some(y)) => 0x8007 | x << 8 | y << 4,
+            ("<<=", Some(y)) => 0x800E | x << 8 | y << 4,
+            ("|=" | "&=" | "^=" | ">>=" | "=-" | "<<=", None) => {
+                return Err(Self::error(&rhs, format!("expected a register, found '{}'", rhs.text)));
+            }
+            _ => return Err(Self::error(&op, format!("unknown operator '{}'", op.text)))
+        };
+
+        self.emit(&token, opcode)
+    }
+
+    fn index(&mut self, token: &Token) -> Result<(), AsmError> {
+        let op: Token = self.next()?;
+
+        match op.text.as_str() {
+            "+=" => self.emit_fx(token, 0x1E),
+            ":=" => {
+                let rhs: Token = self.next()?;
+
+                match rhs.text.as_str() {
+                    "hex" => self.emit_fx(token, 0x29),
+                    "bighex" => self.emit_fx(token, 0x30),
+                    "long" => {
+                        let addr: Token = self.next()?;
+
+                        self.emit(token, 0xF000)?;
+
+                        let offset: usize = self.pos;
+                        self.emit(token, 0x0000)?;
+
+                        match self.address_value(&addr)? {
+                            Some(value) => {
+                                self.rom[offset] = (value >> 8) as u8;
+                                self.rom[offset + 1] = value as u8;
+                            }
+                            None => self.fixups.push((addr, Fixup::Long(offset)))
+                        }
+
+                        Ok(())
+                    }
+                    _ => {
+                        self.tokens.push_front(rhs);
+                        self.emit_address(0xA000)
+                    }
+                }
+            }
+            _ => Err(Self::error(&op, format!("unknown operator '{}'", op.text)))
+        }
+    }
+
+    fn conditional(&mut self, token: &Token) -> Result<(), AsmError> {
+        let skip: u16 = self.condition()?;
+        let keyword: Token = self.next()?;
+
+        match keyword.text.as_str() {
+            "then" => self.emit(token, skip),
+            "begin" => {
+                let jump: usize = self.pos + 2;
+
+                self.emit(token, Self::complement(skip))?;
+                self.emit(token, 0x1000)?;
+                self.blocks.push((token.clone(), Block::If { jump }));
+
+                Ok(())
+            }
+            _ => Err(Self::error(&keyword, "expected 'then' or 'begin'"))
+        }
+    }
+
+    // skip instruction that runs the next statement only when the condition holds
+    fn condition(&mut self) -> Result<u16, AsmError> {
+        let x: u16 = self.register()? as u16;
+        let op: Token = self.next()?;
+
+        match op.text.as_str() {
+            "key" => Ok(0xE0A1 | x << 8),
+            "-key" => Ok(0xE09E | x << 8),
+            "==" | "!=" => {
+                let rhs: Token = self.next()?;
+                let equal: bool = op.text == "==";
+
+                if self.is_register(&rhs.text) {
+                    let y: u16 = self.register_value(&rhs)? as u16;
+                    Ok(if equal { 0x9000 } else { 0x5000 } | x << 8 | y << 4)
+                } else {
+                    let nn: u16 = self.byte_value(&rhs)? as u16;
+                    Ok(if equal { 0x4000 } else { 0x3000 } | x << 8 | nn)
+                }
+            }
+            _ => Err(Self::error(&op, format!("unsupported comparison '{}'", op.text)))
+        }
+    }
+
+    fn complement(skip: u16) -> u16 {
+        match skip >> 12 {
+            0x3 => skip ^ 0x7000,           // 3XNN <-> 4XNN
+            0x4 => skip ^ 0x7000,
+            0x5 => skip ^ 0xC000,           // 5XY0 <-> 9XY0
+            0x9 => skip ^ 0xC000,
+            _ => skip ^ (0x9E ^ 0xA1)       // EX9E <-> EXA1
+        }
+    }
+
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name: Token = self.next()?;
+        self.check_name(&name)?;
+
+        let mut params: Vec<String> = Vec::new();
+
+        loop {
+            let param: Token = self.next()?;
+
+            if param.text == "{" {
+                break;
+            }
+
+            params.push(param.text);
+        }
+
+        let mut body: Vec<Token> = Vec::new();
+        let mut depth: usize = 1;
+
+        loop {
+            let token: Token = self.next()?;
+
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => ()
+            }
+
+            body.push(token);
+        }
+
+        self.macros.insert(name.text, Macro { params, body });
+
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, token: &Token) -> Result<(), AsmError> {
+        self.expansions += 1;
+
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(Self::error(token, "too many macro expansions, is a macro recursive?"));
+        }
+
+        let mut args: HashMap<String, Token> = HashMap::new();
+        let params: Vec<String> = self.macros[&token.text].params.clone();
+
+        for param in params {
+            let arg: Token = self.next()?;
+            args.insert(param, arg);
+        }
+
+        let expanded: Vec<Token> = self.macros[&token.text].body
+            .iter()
+            .map(|body_token| args.get(&body_token.text).unwrap_or(body_token).clone())
+            .collect();
+
+        for body_token in expanded.into_iter().rev() {
+            self.tokens.push_front(body_token);
+        }
+
+        Ok(())
+    }
+
+    fn emit_address(&mut self, prefix: u16) -> Result<(), AsmError> {
+        let addr: Token = self.next()?;
+
+        match self.address_value(&addr)? {
+            Some(value) if value > 0xFFF => {
+                Err(Self::error(&addr, format!("address 0x{:X} is out of 12 bit range", value)))
+            }
+            Some(value) => self.emit(&addr, prefix | value),
+            None => {
+                self.fixups.push((addr.clone(), Fixup::Address(self.pos)));
+                self.emit(&addr, prefix)
+            }
+        }
+    }
+
+    // known address, or None for a label that is defined later
+    fn address_value(&self, token: &Token) -> Result<Option<u16>, AsmError> {
+        if let Some(addr) = self.labels.get(&token.text) {
+            return Ok(Some(*addr));
+        }
+
+        if Self::number(&token.text).is_some() || self.consts.contains_key(&token.text) {
+            let value: i64 = self.value(token)?;
+
+            return match u16::try_from(value) {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(Self::error(token, format!("address {} is out of range", value)))
+            };
+        }
+
+        self.check_name(token)?;
+
+        Ok(None)
+    }
+
+    fn emit_fx(&mut self, token: &Token, nn: u16) -> Result<(), AsmError> {
+        let x: u16 = self.register()? as u16;
+        self.emit(token, 0xF000 | x << 8 | nn)
+    }
+
+    // `save vx` / `load vx`, or the XO-CHIP `save vx - vy` / `load vx - vy`
+    fn emit_range(&mut self, token: &Token, nn: u16, range_opcode: u16) -> Result<(), AsmError> {
+        let x: u16 = self.register()? as u16;
+
+        if self.tokens.front().is_some_and(|next| next.text == "-") {
+            self.next()?;
+
+            let y: u16 = self.register()? as u16;
+            return self.emit(token, range_opcode | x << 8 | y << 4);
+        }
+
+        self.emit(token, 0xF000 | x << 8 | nn)
+    }
+
+    fn patch_jump(&mut self, offset: usize, token: &Token) -> Result<(), AsmError> {
+        let addr: u16 = self.here(token)?;
+
+        if addr > 0xFFF {
+            return Err(Self::error(token, "block ends outside of 12 bit range"));
+        }
+
+        self.rom[offset] = 0x10 | (addr >> 8) as u8;
+        self.rom[offset + 1] = addr as u8;
+
+        Ok(())
+    }
+
+    fn emit(&mut self, token: &Token, word: u16) -> Result<(), AsmError> {
+        self.emit_byte(token, (word >> 8) as u8)?;
+        self.emit_byte(token, word as u8)
+    }
+
+    fn emit_byte(&mut self, token: &Token, byte: u8) -> Result<(), AsmError> {
+        self.here(token)?;
+
+        if self.pos >= self.rom.len() {
+            self.rom.resize(self.pos + 1, 0);
+        }
+
+        self.rom[self.pos] = byte;
+        self.pos += 1;
+
+        Ok(())
+    }
+
+    fn here(&self, token: &Token) -> Result<u16, AsmError> {
+        u16::try_from(ENTRY_POINT as usize + self.pos)
+            .map_err(|_| Self::error(token, "program does not fit into 64 KiB"))
+    }
+
+    fn register(&mut self) -> Result<u8, AsmError> {
+        let token: Token = self.next()?;
+        self.register_value(&token)
+    }
+
+    fn register_value(&self, token: &Token) -> Result<u8, AsmError> {
+        if let Some(register) = self.aliases.get(&token.text) {
+            return Ok(*register);
+        }
+
+        let text: String = token.text.to_lowercase();
+
+        match text.strip_prefix('v') {
+            Some(digit) if digit.len() == 1 => u8::from_str_radix(digit, 16)
+                .map_err(|_| Self::error(token, format!("expected a register, found '{}'", token.text))),
+            _ => Err(Self::error(token, format!("expected a register, found '{}'", token.text)))
+        }
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        let lower: String = text.to_lowercase();
+
+        self.aliases.contains_key(text) || (
+            lower.len() == 2
+            && lower.starts_with('v')
+            && lower.chars().nth(1).is_some_and(|c| c.is_ascii_hexdigit())
+        )
+    }
+
+    fn byte(&mut self) -> Result<u8, AsmError> {
+        let token: Token = self.next()?;
+        self.byte_value(&token)
+    }
+
+    fn byte_value(&self, token: &Token) -> Result<u8, AsmError> {
+        let value: i64 = self.value(token)?;
+
+        if !(-128..=255).contains(&value) {
+            return Err(Self::error(token, format!("{} does not fit into a byte", value)));
+        }
+
+        Ok(value as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let token: Token = self.next()?;
+        let value: i64 = self.value(&token)?;
+
+        if !(0..=15).contains(&value) {
+            return Err(Self::error(&token, format!("{} does not fit into 4 bits", value)));
+        }
+
+        Ok(value as u16)
+    }
+
+    fn value(&self, token: &Token) -> Result<i64, AsmError> {
+        if let Some(value) = Self::number(&token.text) {
+            return Ok(value);
+        }
+
+        if let Some(value) = self.consts.get(&token.text) {
+            return Ok(*value);
+        }
+
+        if let Some(addr) = self.labels.get(&token.text) {
+            return Ok(*addr as i64);
+        }
+
+        Err(Self::error(token, format!("unknown value '{}'", token.text)))
+    }
+
+    fn number(text: &str) -> Option<i64> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text)
+        };
+
+        let value: i64 = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            i64::from_str_radix(hex, 16).ok()?
+        } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+            i64::from_str_radix(bin, 2).ok()?
+        } else {
+            digits.parse::<i64>().ok()?
+        };
+
+        Some(if negative { -value } else { value })
+    }
+
+    fn check_name(&self, token: &Token) -> Result<(), AsmError> {
+        let mut chars = token.text.chars();
+        let valid: bool = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+        if !valid || self.is_register(&token.text) {
+            return Err(Self::error(token, format!("unexpected '{}'", token.text)));
+        }
+
+        Ok(())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token: Token = self.next()?;
+
+        if token.text != text {
+            return Err(Self::error(&token, format!("expected '{}', found '{}'", text, token.text)));
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        self.tokens
+            .pop_front()
+            .ok_or_else(|| Self::error(&self.last, "unexpected end of file"))
+    }
+
+    fn error(token: &Token, message: impl Into<String>) -> AsmError {
+        AsmError {
+            line: token.line,
+            column: token.column,
+            message: message.into()
+        }
+    }
+}
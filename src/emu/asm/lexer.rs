@@ -0,0 +1,39 @@
+/// Whitespace separated word of Octo source with its 1-based position.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub column: usize
+}
+
+
+/// Splits source into tokens, `#` starts a comment that runs to the end of the line.
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let code: &str = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line
+        };
+
+        let mut start: Option<usize> = None;
+
+        for (index, c) in code.char_indices().chain(std::iter::once((code.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (true, Some(begin)) => {
+                    tokens.push(Token {
+                        text: code[begin..index].to_string(),
+                        line: line_index + 1,
+                        column: code[..begin].chars().count() + 1
+                    });
+                    start = None;
+                }
+                (false, None) => start = Some(index),
+                _ => ()
+            }
+        }
+    }
+
+    tokens
+}
@@ -0,0 +1,111 @@
+//! Assembler for a subset of the Octo language.
+//!
+//! Supported are labels, `:const`, `:alias`, `:macro`, `:org`, `:call`,
+//! `:byte`, raw data bytes, every CHIP-8, SUPER-CHIP and XO-CHIP statement,
+//! `if ... then`, `if ... begin ... else ... end` and `loop ... while ... again`.
+
+mod lexer;
+mod assembler;
+
+use std::{
+    collections::BTreeMap,
+    fmt
+};
+
+
+/// Assembled rom, ready to be loaded at the entry point.
+pub struct Assembly {
+    pub rom: Vec<u8>,
+    pub symbols: SymbolMap
+}
+
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+
+impl std::error::Error for AsmError {}
+
+
+/// Label addresses of an assembled rom.
+///
+/// The text form has one `ADDR NAME` pair per line with the address in hex.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SymbolMap {
+    symbols: BTreeMap<String, u16>
+}
+
+
+impl SymbolMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: String, addr: u16) {
+        self.symbols.insert(name, addr);
+    }
+
+    pub fn get(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+
+    /// First label defined at `addr`, in alphabetical order.
+    pub fn name_of(&self, addr: u16) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|(_, symbol_addr)| **symbol_addr == addr)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.symbols.iter().map(|(name, addr)| (name.as_str(), *addr))
+    }
+
+    /// Parses the text form, malformed lines are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut symbols: SymbolMap = SymbolMap::new();
+
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+
+            if let (Some(addr), Some(name)) = (parts.next(), parts.next()) {
+                if let Ok(addr) = u16::from_str_radix(addr.trim_start_matches("0x"), 16) {
+                    symbols.insert(name.to_string(), addr);
+                }
+            }
+        }
+
+        symbols
+    }
+}
+
+
+impl fmt::Display for SymbolMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut by_addr: Vec<(&str, u16)> = self.iter().collect();
+
+        by_addr.sort_by_key(|(name, addr)| (*addr, *name));
+
+        for (name, addr) in by_addr {
+            writeln!(f, "0x{:04X} {}", addr, name)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    assembler::Assembler::new(lexer::tokenize(source)).run()
+}
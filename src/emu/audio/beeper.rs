@@ -0,0 +1,60 @@
+use super::{
+    Pattern,
+    Tone
+};
+
+
+/// Turns the per-frame buzzer state into samples.
+///
+/// Plays the XO-CHIP pattern once a rom has loaded one, the tone otherwise.
+pub struct Beeper {
+    tone: Tone,
+    pattern: Option<Pattern>,
+    on: bool
+}
+
+
+impl Beeper {
+    pub fn new(tone: Tone) -> Self {
+        Self {
+            tone,
+            pattern: None,
+            on: false
+        }
+    }
+
+    pub fn set_on(&mut self, on: bool) {
+        self.on = on;
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn set_pattern(&mut self, pattern: &Pattern) {
+        match self.pattern.as_mut() {
+            Some(current) => {
+                current.load(*pattern.buffer());
+                current.set_pitch(pattern.pitch());
+            }
+            None => {
+                self.pattern = Some(pattern.clone());
+            }
+        }
+    }
+
+    pub fn render(&mut self, out: &mut [f32], sample_rate: u32) {
+        if !self.on {
+            out.fill(0.0);
+            return;
+        }
+
+        match self.pattern.as_mut() {
+            Some(pattern) => {
+                pattern.render(out, sample_rate);
+                out.iter_mut().for_each(|sample| *sample *= self.tone.volume);
+            }
+            None => self.tone.render(out, sample_rate)
+        }
+    }
+}
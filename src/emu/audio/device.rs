@@ -0,0 +1,82 @@
+use std::{
+    error::Error,
+    sync::{
+        Arc,
+        Mutex
+    }
+};
+use cpal::traits::{
+    DeviceTrait,
+    HostTrait,
+    StreamTrait
+};
+use super::{
+    AudioSink,
+    Beeper,
+    Pattern,
+    Tone
+};
+
+
+/// Plays the beeper on the default output device of the host.
+pub struct DeviceSink {
+    beeper: Arc<Mutex<Beeper>>,     // shared with the audio callback thread
+    _stream: cpal::Stream           // sound stops when the stream is dropped
+}
+
+
+impl DeviceSink {
+    pub fn new(tone: Tone) -> Result<Self, Box<dyn Error>> {
+        let device: cpal::Device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio output device")?;
+
+        let config: cpal::StreamConfig = device.default_output_config()?.into();
+        let channels: usize = config.channels as usize;
+        let sample_rate: u32 = config.sample_rate.0;
+
+        let beeper: Arc<Mutex<Beeper>> = Arc::new(Mutex::new(Beeper::new(tone)));
+        let callback_beeper: Arc<Mutex<Beeper>> = Arc::clone(&beeper);
+        let mut mono: Vec<f32> = Vec::new();
+
+        let stream: cpal::Stream = device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                mono.resize(data.len() / channels, 0.0);
+
+                match callback_beeper.lock() {
+                    Ok(mut beeper) => beeper.render(&mut mono, sample_rate),
+                    Err(_) => mono.fill(0.0)
+                }
+
+                for (frame, sample) in data.chunks_mut(channels).zip(mono.iter()) {
+                    frame.fill(*sample);
+                }
+            },
+            |err| eprintln!("audio stream error: {}", err),
+            None
+        )?;
+
+        stream.play()?;
+
+        Ok(Self {
+            beeper,
+            _stream: stream
+        })
+    }
+}
+
+
+impl AudioSink for DeviceSink {
+    fn buzzer(&mut self, on: bool) {
+        if let Ok(mut beeper) = self.beeper.lock() {
+            beeper.set_on(on);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        if let Ok(mut beeper) = self.beeper.lock() {
+            beeper.set_pattern(pattern);
+        }
+    }
+}
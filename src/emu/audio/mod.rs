@@ -0,0 +1,24 @@
+mod pattern;
+mod tone;
+mod beeper;
+mod sink;
+#[cfg(feature = "sound")]
+mod device;
+
+pub use pattern::{
+    Pattern,
+    PATTERN_SIZE,
+    DEFAULT_PITCH
+};
+pub use tone::{
+    Tone,
+    Waveform
+};
+pub use beeper::Beeper;
+pub use sink::{
+    AudioSink,
+    NullSink,
+    WavSink
+};
+#[cfg(feature = "sound")]
+pub use device::DeviceSink;
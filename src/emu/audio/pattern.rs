@@ -0,0 +1,106 @@
+use super::super::error::Chip8Error;
+use super::super::savestate::{
+    Reader,
+    Writer
+};
+
+pub const PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+
+const PATTERN_BITS: f64 = (PATTERN_SIZE * 8) as f64;
+
+
+/// XO-CHIP 1-bit audio pattern buffer.
+///
+/// The 128 bits loaded by F002 are played in a loop while the sound timer is
+/// non-zero, at a rate of 4000 * 2 ^ ((pitch - 64) / 48) bits per second.
+#[derive(Clone)]
+pub struct Pattern {
+    buffer: [u8; PATTERN_SIZE],
+    pitch: u8,          // set by FX3A
+    position: f64,      // playback position in bits, kept between frames
+    custom: bool        // a rom has loaded its own pattern with F002
+}
+
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Pattern {
+    /// Until a rom loads its own pattern a plain square wave is played.
+    pub fn new() -> Self {
+        Self {
+            buffer: [0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+                     0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF],
+            pitch: DEFAULT_PITCH,
+            position: 0.0,
+            custom: false
+        }
+    }
+
+    pub fn load(&mut self, buffer: [u8; PATTERN_SIZE]) {
+        self.buffer = buffer;
+        self.custom = true;
+    }
+
+    pub fn is_custom(&self) -> bool {
+        self.custom
+    }
+
+    pub fn buffer(&self) -> &[u8; PATTERN_SIZE] {
+        &self.buffer
+    }
+
+    pub fn save_state(&self, w: &mut Writer) {
+        w.bytes(&self.buffer);
+        w.u8(self.pitch);
+        w.f64(self.position);
+        w.bool(self.custom);
+    }
+
+    pub fn from_state(r: &mut Reader) -> Result<Self, Chip8Error> {
+        let pattern: Pattern = Self {
+            buffer: r.array()?,
+            pitch: r.u8()?,
+            position: r.f64()?,
+            custom: r.bool()?
+        };
+
+        // a NaN fails the range check as well
+        if !(0.0..PATTERN_BITS).contains(&pattern.position) {
+            return Err(Chip8Error::BadSaveState { reason: "audio position outside the pattern" });
+        }
+
+        Ok(pattern)
+    }
+
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Playback rate in bits per second.
+    pub fn rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// Fills `out` with samples in the -1.0..=1.0 range and advances the playback position.
+    pub fn render(&mut self, out: &mut [f32], sample_rate: u32) {
+        let step: f64 = self.rate() / sample_rate as f64;
+
+        for sample in out.iter_mut() {
+            let bit: usize = self.position as usize;
+            let set: bool = self.buffer[bit / 8] & (0x80 >> (bit % 8)) != 0;
+
+            *sample = if set { 1.0 } else { -1.0 };
+            self.position = (self.position + step) % PATTERN_BITS;
+        }
+    }
+}
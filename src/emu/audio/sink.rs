@@ -0,0 +1,123 @@
+use std::{
+    io,
+    io::{
+        Seek,
+        SeekFrom,
+        Write
+    },
+    fs::File
+};
+use super::{
+    Beeper,
+    Pattern,
+    Tone
+};
+
+
+/// Receives the sound state of the machine once every 60hz frame.
+pub trait AudioSink {
+    /// `on` is true while the sound timer is non-zero.
+    fn buzzer(&mut self, on: bool);
+
+    /// Called before `buzzer` on frames where a rom has loaded an XO-CHIP pattern.
+    fn pattern(&mut self, _pattern: &Pattern) {}
+}
+
+
+/// Discards all sound, the default for headless machines.
+pub struct NullSink;
+
+
+impl AudioSink for NullSink {
+    fn buzzer(&mut self, _on: bool) {}
+}
+
+
+/// Renders every frame into a 16-bit mono WAV file.
+///
+/// The header is kept up to date after every frame, so the file stays valid
+/// even when the sink is simply dropped.
+pub struct WavSink<W: Write + Seek> {
+    writer: W,
+    beeper: Beeper,
+    sample_rate: u32,
+    samples: u32,               // written so far
+    buffer: Vec<f32>
+}
+
+
+impl WavSink<File> {
+    pub fn create(path: &str, tone: Tone, sample_rate: u32) -> io::Result<Self> {
+        Self::new(File::create(path)?, tone, sample_rate)
+    }
+}
+
+
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(mut writer: W, tone: Tone, sample_rate: u32) -> io::Result<Self> {
+        Self::write_header(&mut writer, sample_rate, 0)?;
+
+        Ok(Self {
+            writer,
+            beeper: Beeper::new(tone),
+            sample_rate,
+            samples: 0,
+            buffer: vec![0.0; (sample_rate / 60) as usize]
+        })
+    }
+
+    /// Flushes the file and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        for sample in self.buffer.iter() {
+            let value: i16 = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+
+            self.writer.write_all(&value.to_le_bytes())?;
+            self.samples += 1;
+        }
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        Self::write_header(&mut self.writer, self.sample_rate, self.samples)?;
+        self.writer.seek(SeekFrom::End(0))?;
+
+        Ok(())
+    }
+
+    fn write_header(writer: &mut W, sample_rate: u32, samples: u32) -> io::Result<()> {
+        let data_size: u32 = samples * 2;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;                  // PCM
+        writer.write_all(&1u16.to_le_bytes())?;                  // mono
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * 2).to_le_bytes())?;     // byte rate
+        writer.write_all(&2u16.to_le_bytes())?;                  // block align
+        writer.write_all(&16u16.to_le_bytes())?;                 // bits per sample
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())
+    }
+}
+
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn buzzer(&mut self, on: bool) {
+        self.beeper.set_on(on);
+        self.beeper.render(&mut self.buffer, self.sample_rate);
+
+        // a sink has no way to report errors mid-run, a short file is the best we can do
+        let _ = self.write_frame();
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        self.beeper.set_pattern(pattern);
+    }
+}
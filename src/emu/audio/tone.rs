@@ -0,0 +1,56 @@
+use std::f32::consts::TAU;
+
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine
+}
+
+
+/// Beep generator used when the rom does not provide its own XO-CHIP pattern.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Tone {
+    pub frequency: f32,     // hz
+    pub volume: f32,        // 0.0 - 1.0
+    pub waveform: Waveform,
+    phase: f32              // position inside the current period, 0.0 - 1.0
+}
+
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self::new(440.0, 0.25, Waveform::Square)
+    }
+}
+
+
+impl Tone {
+    pub fn new(frequency: f32, volume: f32, waveform: Waveform) -> Self {
+        Self {
+            frequency,
+            volume,
+            waveform,
+            phase: 0.0
+        }
+    }
+
+    pub fn render(&mut self, out: &mut [f32], sample_rate: u32) {
+        let step: f32 = self.frequency / sample_rate as f32;
+
+        for sample in out.iter_mut() {
+            let value: f32 = match self.waveform {
+                Waveform::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+                Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+                Waveform::Sawtooth => 2.0 * self.phase - 1.0,
+                Waveform::Sine => (self.phase * TAU).sin()
+            };
+
+            *sample = value * self.volume;
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
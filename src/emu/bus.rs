@@ -0,0 +1,36 @@
+use super::ram;
+use super::display;
+use super::audio;
+
+
+/// Borrowed view of everything the CPU talks to during a cycle.
+///
+/// The bus is assembled by the machine right before stepping the CPU, so the
+/// CPU never keeps references to memory, framebuffer or keypad between cycles
+/// and the machine can be moved freely.
+pub struct Bus<'a> {
+    pub ram: &'a mut ram::RAM,
+    pub display: &'a mut display::Display,
+    pub keypad: &'a [bool; 16],
+    pub audio: &'a mut audio::Pattern,
+    pub frame: u64              // frames completed by the machine, mixed into CXNN by the VIP generator
+}
+
+
+impl<'a> Bus<'a> {
+    pub fn new(
+        ram: &'a mut ram::RAM,
+        display: &'a mut display::Display,
+        keypad: &'a [bool; 16],
+        audio: &'a mut audio::Pattern,
+        frame: u64
+    ) -> Self {
+        Self {
+            ram,
+            display,
+            keypad,
+            audio,
+            frame
+        }
+    }
+}
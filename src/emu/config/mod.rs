@@ -0,0 +1,398 @@
+//! Settings file, `$XDG_CONFIG_HOME/chip-8-emu/config.toml` by default.
+//!
+//! Top level keys apply to every rom. A `[rom.<hash>]` table overrides them
+//! for the rom with that hash, as printed by `chip-8-emu --hash <rom>`.
+//! Command line flags override both. Quirks and key bindings are applied
+//! layer by layer on top of the platform preset and the layout or keymap
+//! file, quirk values are checked when the machine is built. Relative paths
+//! are relative to the file. Controller buttons are bound by name, buttons
+//! the driver has no name for as `Button<code>`.
+//!
+//! ```toml
+//! platform = "vip"
+//! instructions-per-frame = 10
+//! window = [1280, 640]
+//! palette = "000000,ffffff"
+//! layout = "azerty"
+//!
+//! [quirks]
+//! display-wait = false
+//!
+//! [rom.5d2c6f0a93b1e7c4]
+//! name = "Blinky"
+//! platform = "schip"
+//! scale = 8
+//! quirks.shift = true
+//!
+//! [rom.5d2c6f0a93b1e7c4.keys]
+//! 2 = "Up"
+//! 4 = ["Left", "H"]
+//! 5 = "Space"
+//!
+//! [rom.5d2c6f0a93b1e7c4.gamepad]
+//! 5 = ["South", "Button704"]
+//! ```
+
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap
+    },
+    env,
+    fs,
+    num::NonZeroU32,
+    path::{
+        Path,
+        PathBuf
+    },
+    str::FromStr
+};
+use serde::{
+    Deserialize,
+    Deserializer,
+    de::Error
+};
+use super::{
+    Machine,
+    display,
+    keymap
+};
+use super::cpu::{
+    quirks::Quirks,
+    rng::Algorithm
+};
+use super::error::Chip8Error;
+use super::platform::Platform;
+
+
+/// One layer of settings, None leaves the value of the layer below.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Settings {
+    pub platform: Option<Platform>,
+    pub quirks: Vec<(String, String)>,          // name and value, applied in order
+    pub instructions_per_frame: Option<u32>,
+    pub window_size: Option<(u32, u32)>,
+    pub palette: Option<display::Palette>,
+    pub layout: Option<keymap::Layout>,
+    pub keymap: Option<PathBuf>,                // keymap file, used instead of the layout
+    pub keys: Vec<(u8, String)>,                // hex key and host key, bound in order
+    pub gamepad: Vec<(u8, String)>,             // hex key and controller button, bound in order
+    pub seed: Option<u64>,
+    pub rng: Option<Algorithm>
+}
+
+
+impl Settings {
+    /// Puts `other` on top of these settings.
+    pub fn merge(&mut self, other: &Settings) {
+        self.platform = other.platform.or(self.platform);
+        self.quirks.extend(other.quirks.iter().cloned());
+        self.instructions_per_frame = other.instructions_per_frame.or(self.instructions_per_frame);
+        self.window_size = other.window_size.or(self.window_size);
+        self.palette = other.palette.or(self.palette);
+
+        // a layout or keymap file replaces both of the layer below
+        if other.layout.is_some() || other.keymap.is_some() {
+            self.layout = other.layout;
+            self.keymap = other.keymap.clone();
+        }
+
+        self.keys.extend(other.keys.iter().cloned());
+        self.gamepad.extend(other.gamepad.iter().cloned());
+        self.seed = other.seed.or(self.seed);
+        self.rng = other.rng.or(self.rng);
+    }
+
+    pub fn quirks(&self) -> Result<Quirks, String> {
+        let mut quirks: Quirks = self.platform.unwrap_or_default().quirks();
+
+        for (name, value) in self.quirks.iter() {
+            quirks.set(name, value)?;
+        }
+
+        Ok(quirks)
+    }
+
+    pub fn keymap(&self) -> Result<keymap::KeyMap, String> {
+        let mut keymap: keymap::KeyMap = match self.keymap.as_ref() {
+            Some(path) => keymap::KeyMap::load(&path.to_string_lossy())
+                .map_err(|err| format!("{}: {}", path.display(), err))?,
+            None => keymap::KeyMap::with_layout(self.layout.unwrap_or_default())
+        };
+
+        for (key, host) in self.keys.iter() {
+            keymap.bind(host, *key);
+        }
+
+        Ok(keymap)
+    }
+
+    /// Default controller bindings with the bindings of every layer on top.
+    pub fn gamepad_map(&self) -> keymap::KeyMap {
+        let mut gamepad_map: keymap::KeyMap = keymap::KeyMap::gamepad();
+
+        for (key, button) in self.gamepad.iter() {
+            gamepad_map.bind(button, *key);
+        }
+
+        gamepad_map
+    }
+
+    /// Powered off machine with these settings.
+    pub fn machine(&self) -> Result<Machine, String> {
+        let mut machine: Machine = Machine::with_platform_quirks(self.platform.unwrap_or_default(), self.quirks()?);
+
+        if let Some(instructions) = self.instructions_per_frame {
+            machine.set_instructions_per_frame(instructions);
+        }
+
+        if let Some((width, height)) = self.window_size {
+            machine.set_window_size(width, height);
+        }
+
+        if let Some(palette) = self.palette {
+            machine.set_palette(palette);
+        }
+
+        machine.set_keymap(self.keymap()?);
+        machine.set_gamepad_map(self.gamepad_map());
+
+        if let Some(seed) = self.seed {
+            machine.set_seed(seed);
+        }
+
+        if let Some(algorithm) = self.rng {
+            machine.set_rng_algorithm(algorithm);
+        }
+
+        Ok(machine)
+    }
+}
+
+
+// one table of the file, R holds the rom tables of the top level
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Table<R> {
+    #[serde(rename = "name")]
+    _name: Option<String>,      // only for the reader of the file
+    #[serde(deserialize_with = "parsed")]
+    platform: Option<Platform>,
+    #[serde(deserialize_with = "quirks")]
+    quirks: Vec<(String, String)>,
+    instructions_per_frame: Option<u32>,
+    scale: Option<NonZeroU32>,
+    window: Option<(NonZeroU32, NonZeroU32)>,
+    #[serde(deserialize_with = "palette")]
+    palette: Option<display::Palette>,
+    #[serde(deserialize_with = "parsed")]
+    layout: Option<keymap::Layout>,
+    keymap: Option<PathBuf>,
+    #[serde(deserialize_with = "bindings")]
+    keys: Vec<(u8, String)>,
+    #[serde(deserialize_with = "bindings")]
+    gamepad: Vec<(u8, String)>,
+    seed: Option<u64>,
+    #[serde(deserialize_with = "parsed")]
+    rng: Option<Algorithm>,
+    rom: R
+}
+
+
+impl<R> Table<R> {
+    fn settings(self, dir: &Path) -> Settings {
+        let scaled = |scale: NonZeroU32| {
+            (display::LORES_WIDTH as u32 * scale.get(), display::LORES_HEIGHT as u32 * scale.get())
+        };
+
+        Settings {
+            platform: self.platform,
+            quirks: self.quirks,
+            instructions_per_frame: self.instructions_per_frame,
+            window_size: self.window
+                .map(|(width, height)| (width.get(), height.get()))
+                .or(self.scale.map(scaled)),
+            palette: self.palette,
+            layout: self.layout,
+            keymap: self.keymap.map(|path| dir.join(path)),
+            keys: self.keys,
+            gamepad: self.gamepad,
+            seed: self.seed,
+            rng: self.rng
+        }
+    }
+}
+
+
+/// Hash of a `[rom.<hash>]` table.
+#[derive(PartialEq, Eq, Hash)]
+struct RomHash(u64);
+
+
+impl<'de> Deserialize<'de> for RomHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hash: String = String::deserialize(deserializer)?;
+
+        u64::from_str_radix(&hash, 16)
+            .map(RomHash)
+            .map_err(|_| D::Error::custom(format!("'{}' is not a rom hash", hash)))
+    }
+}
+
+
+/// Hex key of a keys or gamepad table.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct KeypadKey(u8);
+
+
+impl<'de> Deserialize<'de> for KeypadKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key: String = String::deserialize(deserializer)?;
+
+        u8::from_str_radix(&key, 16)
+            .ok()
+            .filter(|key| *key < 16)
+            .map(KeypadKey)
+            .ok_or(D::Error::custom(format!("'{}' is not a keypad key", key)))
+    }
+}
+
+
+/// Rom tables inside a rom table.
+#[derive(Default)]
+struct NoRoms;
+
+
+impl<'de> Deserialize<'de> for NoRoms {
+    fn deserialize<D: Deserializer<'de>>(_deserializer: D) -> Result<Self, D::Error> {
+        Err(D::Error::custom("rom tables cannot be nested"))
+    }
+}
+
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>)
+}
+
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(name) => vec![name],
+            OneOrMany::Many(names) => names
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuirkValue {
+    Flag(bool),
+    Number(u64),
+    Name(String)
+}
+
+
+// platform, layout or generator by name
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>
+{
+    String::deserialize(deserializer)?.parse().map(Some).map_err(D::Error::custom)
+}
+
+
+// values are checked by Settings::quirks
+fn quirks<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+    let quirks: BTreeMap<String, QuirkValue> = BTreeMap::deserialize(deserializer)?;
+
+    Ok(quirks
+        .into_iter()
+        .map(|(name, value)| {
+            let value: String = match value {
+                QuirkValue::Flag(flag) => flag.to_string(),
+                QuirkValue::Number(number) => number.to_string(),
+                QuirkValue::Name(name) => name
+            };
+
+            (name, value)
+        })
+        .collect())
+}
+
+
+// `"000000,ffffff"` or `["000000", "ffffff"]`
+fn palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<display::Palette>, D::Error> {
+    let colours: String = OneOrMany::deserialize(deserializer)?.into_vec().join(",");
+
+    display::parse_palette(&colours).map(Some).map_err(D::Error::custom)
+}
+
+
+// `key = "name"` or `key = ["name", ...]` of a keys or gamepad table
+fn bindings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(u8, String)>, D::Error> {
+    let table: BTreeMap<KeypadKey, OneOrMany> = BTreeMap::deserialize(deserializer)?;
+
+    Ok(table
+        .into_iter()
+        .flat_map(|(KeypadKey(key), names)| names.into_vec().into_iter().map(move |name| (key, name)))
+        .collect())
+}
+
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Config {
+    pub global: Settings,
+    pub roms: HashMap<u64, Settings>    // by rom hash
+}
+
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip-8-emu/config.toml`, falling back to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home: PathBuf = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+        Some(config_home.join("chip-8-emu").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Chip8Error> {
+        Self::parse(&fs::read_to_string(path)?, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Parses a config file, relative paths are resolved against `dir`.
+    pub fn parse(text: &str, dir: &Path) -> Result<Self, Chip8Error> {
+        let mut table: Table<HashMap<RomHash, Table<NoRoms>>> = toml::from_str(text).map_err(|err| {
+            let line: usize = err.span().map_or(0, |span| text[..span.start].matches('\n').count() + 1);
+
+            Chip8Error::BadConfig { line, reason: err.message().to_string() }
+        })?;
+        let roms: HashMap<RomHash, Table<NoRoms>> = std::mem::take(&mut table.rom);
+
+        Ok(Self {
+            global: table.settings(dir),
+            roms: roms
+                .into_iter()
+                .map(|(RomHash(hash), table)| (hash, table.settings(dir)))
+                .collect()
+        })
+    }
+
+    /// Global settings with the overrides for the rom with `rom_hash`.
+    pub fn settings(&self, rom_hash: u64) -> Settings {
+        let mut settings: Settings = self.global.clone();
+
+        if let Some(rom) = self.roms.get(&rom_hash) {
+            settings.merge(rom);
+        }
+
+        settings
+    }
+}
@@ -1,43 +1,305 @@
-pub struct Instruction {
-    pub opcode: u16,
-    pub nnn: u16,       // 12 bit addr constant
-    pub nn: u8,         // 8 bit constant
-    pub n: u8,          // 4 bit constant
-    pub x: usize,       // 4 bit register identifier
-    pub y: usize        // 4 bit register identifier
+use std::fmt;
+
+
+/// Decoded instruction.
+///
+/// `x` and `y` are register numbers, `nnn` addresses, `nn` 8 bit and `n`
+/// 4 bit constants, named after the nibbles of the raw opcode.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Op {
+    Sys { nnn: u16 },                   // 0NNN machine code routine, not supported
+    ClearScreen,                        // 00E0
+    Return,                             // 00EE
+    ScrollDown { n: u8 },               // 00CN SUPER-CHIP
+    ScrollUp { n: u8 },                 // 00DN XO-CHIP
+    ScrollRight,                        // 00FB SUPER-CHIP
+    ScrollLeft,                         // 00FC SUPER-CHIP
+    Exit,                               // 00FD SUPER-CHIP
+    Lores,                              // 00FE SUPER-CHIP
+    Hires,                              // 00FF SUPER-CHIP
+    Jump { nnn: u16 },                  // 1NNN
+    Call { nnn: u16 },                  // 2NNN
+    SkipEqImm { x: u8, nn: u8 },        // 3XNN
+    SkipNeImm { x: u8, nn: u8 },        // 4XNN
+    SkipEqReg { x: u8, y: u8 },         // 5XY0
+    SaveRange { x: u8, y: u8 },         // 5XY2 XO-CHIP
+    LoadRange { x: u8, y: u8 },         // 5XY3 XO-CHIP
+    LoadImm { x: u8, nn: u8 },          // 6XNN
+    AddImm { x: u8, nn: u8 },           // 7XNN
+    Move { x: u8, y: u8 },              // 8XY0
+    Or { x: u8, y: u8 },                // 8XY1
+    And { x: u8, y: u8 },               // 8XY2
+    Xor { x: u8, y: u8 },               // 8XY3
+    Add { x: u8, y: u8 },               // 8XY4
+    Sub { x: u8, y: u8 },               // 8XY5
+    ShiftRight { x: u8, y: u8 },        // 8XY6
+    SubReverse { x: u8, y: u8 },        // 8XY7
+    ShiftLeft { x: u8, y: u8 },         // 8XYE
+    SkipNeReg { x: u8, y: u8 },         // 9XY0
+    LoadIndex { nnn: u16 },             // ANNN
+    JumpOffset { x: u8, nnn: u16 },     // BNNN, x is only used by the jump quirk
+    Random { x: u8, nn: u8 },           // CXNN
+    DrawSprite { x: u8, y: u8, n: u8 }, // DXYN
+    SkipKeyPressed { x: u8 },           // EX9E
+    SkipKeyReleased { x: u8 },          // EXA1
+    LoadLongIndex { nnnn: u16 },        // F000 NNNN XO-CHIP
+    SelectPlanes { n: u8 },             // FN01 XO-CHIP
+    LoadAudio,                          // F002 XO-CHIP
+    GetDelay { x: u8 },                 // FX07
+    WaitKey { x: u8 },                  // FX0A
+    SetDelay { x: u8 },                 // FX15
+    SetSound { x: u8 },                 // FX18
+    AddIndex { x: u8 },                 // FX1E
+    FontChar { x: u8 },                 // FX29
+    BigFontChar { x: u8 },              // FX30 SUPER-CHIP
+    Bcd { x: u8 },                      // FX33
+    SetPitch { x: u8 },                 // FX3A XO-CHIP
+    Store { x: u8 },                    // FX55
+    Load { x: u8 },                     // FX65
+    SaveFlags { x: u8 },                // FX75 SUPER-CHIP
+    LoadFlags { x: u8 },                // FX85 SUPER-CHIP
+    Unknown { opcode: u16 }
 }
 
 
-impl Instruction {
-    pub fn new() -> Self {
-        Self {
-            opcode: 0,
-            nnn: 0,
-            nn: 0,
-            n: 0,
-            x: 0,
-            y: 0
-        }
-    }
+/// Mnemonic flavour used by the disassembler.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Syntax {
+    #[default]
+    Octo,
+    Classic     // Cowgod's technical reference style
+}
 
-    pub fn from(opcode1: u8, opcode2: u8) -> Self {
-        let left: u16 = (opcode1 as u16) << 8;
-        let right: u16 = opcode2 as u16;
-        let opcode = left | right;
 
+impl Op {
+    /// Decodes an opcode. `next` is the following word, only used by the
+    /// four byte XO-CHIP long load.
+    pub fn decode(opcode: u16, next: u16) -> Self {
         let nnn: u16 = opcode & 0x0FFF;
-        let nn: u8 = opcode as u8 & 0x0FF;
+        let nn: u8 = opcode as u8;
         let n: u8 = (opcode & 0x0F) as u8;
-        let x: usize = ((opcode >> 8)  & 0x0F) as usize;
-        let y: usize = ((opcode >> 4) & 0x0F) as usize;
-
-        Self {
-            opcode,
-            nnn,
-            nn,
-            n,
-            x,
-            y
+        let x: u8 = ((opcode >> 8) & 0x0F) as u8;
+        let y: u8 = ((opcode >> 4) & 0x0F) as u8;
+
+        match (opcode >> 12, n) {
+            (0x0, _) => match opcode {
+                0x00E0 => Op::ClearScreen,
+                0x00EE => Op::Return,
+                0x00FB => Op::ScrollRight,
+                0x00FC => Op::ScrollLeft,
+                0x00FD => Op::Exit,
+                0x00FE => Op::Lores,
+                0x00FF => Op::Hires,
+                _ if opcode & 0xFFF0 == 0x00C0 => Op::ScrollDown { n },
+                _ if opcode & 0xFFF0 == 0x00D0 => Op::ScrollUp { n },
+                _ => Op::Sys { nnn }
+            },
+            (0x1, _) => Op::Jump { nnn },
+            (0x2, _) => Op::Call { nnn },
+            (0x3, _) => Op::SkipEqImm { x, nn },
+            (0x4, _) => Op::SkipNeImm { x, nn },
+            (0x5, 0x0) => Op::SkipEqReg { x, y },
+            (0x5, 0x2) => Op::SaveRange { x, y },
+            (0x5, 0x3) => Op::LoadRange { x, y },
+            (0x6, _) => Op::LoadImm { x, nn },
+            (0x7, _) => Op::AddImm { x, nn },
+            (0x8, 0x0) => Op::Move { x, y },
+            (0x8, 0x1) => Op::Or { x, y },
+            (0x8, 0x2) => Op::And { x, y },
+            (0x8, 0x3) => Op::Xor { x, y },
+            (0x8, 0x4) => Op::Add { x, y },
+            (0x8, 0x5) => Op::Sub { x, y },
+            (0x8, 0x6) => Op::ShiftRight { x, y },
+            (0x8, 0x7) => Op::SubReverse { x, y },
+            (0x8, 0xE) => Op::ShiftLeft { x, y },
+            (0x9, 0x0) => Op::SkipNeReg { x, y },
+            (0xA, _) => Op::LoadIndex { nnn },
+            (0xB, _) => Op::JumpOffset { x, nnn },
+            (0xC, _) => Op::Random { x, nn },
+            (0xD, _) => Op::DrawSprite { x, y, n },
+            (0xE, _) => match nn {
+                0x9E => Op::SkipKeyPressed { x },
+                0xA1 => Op::SkipKeyReleased { x },
+                _ => Op::Unknown { opcode }
+            },
+            (0xF, _) => match nn {
+                0x00 if x == 0 => Op::LoadLongIndex { nnnn: next },
+                0x01 => Op::SelectPlanes { n: x },
+                0x02 if x == 0 => Op::LoadAudio,
+                0x07 => Op::GetDelay { x },
+                0x0A => Op::WaitKey { x },
+                0x15 => Op::SetDelay { x },
+                0x18 => Op::SetSound { x },
+                0x1E => Op::AddIndex { x },
+                0x29 => Op::FontChar { x },
+                0x30 => Op::BigFontChar { x },
+                0x33 => Op::Bcd { x },
+                0x3A => Op::SetPitch { x },
+                0x55 => Op::Store { x },
+                0x65 => Op::Load { x },
+                0x75 => Op::SaveFlags { x },
+                0x85 => Op::LoadFlags { x },
+                _ => Op::Unknown { opcode }
+            },
+            _ => Op::Unknown { opcode }
+        }
+    }
+
+    /// Size in bytes, four for the XO-CHIP long load and two for everything else.
+    pub fn size(&self) -> u16 {
+        match self {
+            Op::LoadLongIndex { .. } => 4,
+            _ => 2
+        }
+    }
+
+    pub fn disassemble(&self, syntax: Syntax) -> Disassembly {
+        Disassembly {
+            op: *self,
+            syntax
+        }
+    }
+
+    fn fmt_octo(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // skips are written as the condition under which the next instruction runs
+        match *self {
+            Op::Sys { nnn } => write!(f, "0x{:02X} 0x{:02X}", nnn >> 8, nnn & 0xFF),
+            Op::ClearScreen => write!(f, "clear"),
+            Op::Return => write!(f, "return"),
+            Op::ScrollDown { n } => write!(f, "scroll-down {}", n),
+            Op::ScrollUp { n } => write!(f, "scroll-up {}", n),
+            Op::ScrollRight => write!(f, "scroll-right"),
+            Op::ScrollLeft => write!(f, "scroll-left"),
+            Op::Exit => write!(f, "exit"),
+            Op::Lores => write!(f, "lores"),
+            Op::Hires => write!(f, "hires"),
+            Op::Jump { nnn } => write!(f, "jump 0x{:03X}", nnn),
+            Op::Call { nnn } => write!(f, ":call 0x{:03X}", nnn),
+            Op::SkipEqImm { x, nn } => write!(f, "if v{:X} != 0x{:02X} then", x, nn),
+            Op::SkipNeImm { x, nn } => write!(f, "if v{:X} == 0x{:02X} then", x, nn),
+            Op::SkipEqReg { x, y } => write!(f, "if v{:X} != v{:X} then", x, y),
+            Op::SaveRange { x, y } => write!(f, "save v{:X} - v{:X}", x, y),
+            Op::LoadRange { x, y } => write!(f, "load v{:X} - v{:X}", x, y),
+            Op::LoadImm { x, nn } => write!(f, "v{:X} := 0x{:02X}", x, nn),
+            Op::AddImm { x, nn } => write!(f, "v{:X} += 0x{:02X}", x, nn),
+            Op::Move { x, y } => write!(f, "v{:X} := v{:X}", x, y),
+            Op::Or { x, y } => write!(f, "v{:X} |= v{:X}", x, y),
+            Op::And { x, y } => write!(f, "v{:X} &= v{:X}", x, y),
+            Op::Xor { x, y } => write!(f, "v{:X} ^= v{:X}", x, y),
+            Op::Add { x, y } => write!(f, "v{:X} += v{:X}", x, y),
+            Op::Sub { x, y } => write!(f, "v{:X} -= v{:X}", x, y),
+            Op::ShiftRight { x, y } => write!(f, "v{:X} >>= v{:X}", x, y),
+            Op::SubReverse { x, y } => write!(f, "v{:X} =- v{:X}", x, y),
+            Op::ShiftLeft { x, y } => write!(f, "v{:X} <<= v{:X}", x, y),
+            Op::SkipNeReg { x, y } => write!(f, "if v{:X} == v{:X} then", x, y),
+            Op::LoadIndex { nnn } => write!(f, "i := 0x{:03X}", nnn),
+            Op::JumpOffset { nnn, .. } => write!(f, "jump0 0x{:03X}", nnn),
+            Op::Random { x, nn } => write!(f, "v{:X} := random 0x{:02X}", x, nn),
+            Op::DrawSprite { x, y, n } => write!(f, "sprite v{:X} v{:X} {}", x, y, n),
+            Op::SkipKeyPressed { x } => write!(f, "if v{:X} -key then", x),
+            Op::SkipKeyReleased { x } => write!(f, "if v{:X} key then", x),
+            Op::LoadLongIndex { nnnn } => write!(f, "i := long 0x{:04X}", nnnn),
+            Op::SelectPlanes { n } => write!(f, "plane {}", n),
+            Op::LoadAudio => write!(f, "audio"),
+            Op::GetDelay { x } => write!(f, "v{:X} := delay", x),
+            Op::WaitKey { x } => write!(f, "v{:X} := key", x),
+            Op::SetDelay { x } => write!(f, "delay := v{:X}", x),
+            Op::SetSound { x } => write!(f, "buzzer := v{:X}", x),
+            Op::AddIndex { x } => write!(f, "i += v{:X}", x),
+            Op::FontChar { x } => write!(f, "i := hex v{:X}", x),
+            Op::BigFontChar { x } => write!(f, "i := bighex v{:X}", x),
+            Op::Bcd { x } => write!(f, "bcd v{:X}", x),
+            Op::SetPitch { x } => write!(f, "pitch := v{:X}", x),
+            Op::Store { x } => write!(f, "save v{:X}", x),
+            Op::Load { x } => write!(f, "load v{:X}", x),
+            Op::SaveFlags { x } => write!(f, "saveflags v{:X}", x),
+            Op::LoadFlags { x } => write!(f, "loadflags v{:X}", x),
+            Op::Unknown { opcode } => write!(f, "0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF)
+        }
+    }
+
+    fn fmt_classic(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::Sys { nnn } => write!(f, "SYS {:03X}", nnn),
+            Op::ClearScreen => write!(f, "CLS"),
+            Op::Return => write!(f, "RET"),
+            Op::ScrollDown { n } => write!(f, "SCD {}", n),
+            Op::ScrollUp { n } => write!(f, "SCU {}", n),
+            Op::ScrollRight => write!(f, "SCR"),
+            Op::ScrollLeft => write!(f, "SCL"),
+            Op::Exit => write!(f, "EXIT"),
+            Op::Lores => write!(f, "LOW"),
+            Op::Hires => write!(f, "HIGH"),
+            Op::Jump { nnn } => write!(f, "JP {:03X}", nnn),
+            Op::Call { nnn } => write!(f, "CALL {:03X}", nnn),
+            Op::SkipEqImm { x, nn } => write!(f, "SE V{:X}, {:02X}", x, nn),
+            Op::SkipNeImm { x, nn } => write!(f, "SNE V{:X}, {:02X}", x, nn),
+            Op::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Op::SaveRange { x, y } => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            Op::LoadRange { x, y } => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            Op::LoadImm { x, nn } => write!(f, "LD V{:X}, {:02X}", x, nn),
+            Op::AddImm { x, nn } => write!(f, "ADD V{:X}, {:02X}", x, nn),
+            Op::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Op::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Op::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Op::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Op::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Op::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Op::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Op::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Op::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Op::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Op::LoadIndex { nnn } => write!(f, "LD I, {:03X}", nnn),
+            Op::JumpOffset { nnn, .. } => write!(f, "JP V0, {:03X}", nnn),
+            Op::Random { x, nn } => write!(f, "RND V{:X}, {:02X}", x, nn),
+            Op::DrawSprite { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Op::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Op::SkipKeyReleased { x } => write!(f, "SKNP V{:X}", x),
+            Op::LoadLongIndex { nnnn } => write!(f, "LD I, {:04X}", nnnn),
+            Op::SelectPlanes { n } => write!(f, "PLANE {}", n),
+            Op::LoadAudio => write!(f, "AUDIO"),
+            Op::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Op::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Op::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Op::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Op::AddIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Op::FontChar { x } => write!(f, "LD F, V{:X}", x),
+            Op::BigFontChar { x } => write!(f, "LD HF, V{:X}", x),
+            Op::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Op::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+            Op::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Op::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Op::SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            Op::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+            Op::Unknown { opcode } => write!(f, "DW {:04X}", opcode)
+        }
+    }
+}
+
+
+/// Octo style by default, `{:#}` gives the classic mnemonics.
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            self.fmt_classic(f)
+        } else {
+            self.fmt_octo(f)
+        }
+    }
+}
+
+
+pub struct Disassembly {
+    op: Op,
+    syntax: Syntax
+}
+
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.syntax {
+            Syntax::Octo => self.op.fmt_octo(f),
+            Syntax::Classic => self.op.fmt_classic(f)
         }
     }
 }
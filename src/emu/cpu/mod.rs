@@ -1,375 +1,798 @@
-mod instruction;
-
-use std::collections::HashMap;
-use instruction::Instruction;
+pub mod instruction;
+pub mod quirks;
+pub mod rng;
+
+use instruction::Op;
+use rng::{
+    Algorithm,
+    Rng
+};
+use quirks::{
+    Quirks,
+    MemoryIncrement,
+    MAX_STACK_DEPTH
+};
+use super::bus::Bus;
+use super::error::{
+    Chip8Error,
+    FaultPolicy
+};
+use super::audio;
+use super::debugger::watch::{
+    Access,
+    AccessKind,
+    Location
+};
 use super::display;
-use super::ram;
+use super::ram::font;
+use super::savestate::{
+    Reader,
+    Writer
+};
+use super::trace::{
+    TraceEntry,
+    TraceSink
+};
 
 pub const ENTRY_POINT: u16 = 0x200;
 const INSTRUCTIONS_PER_SECOND: u32 = 500;
+pub const INSTRUCTIONS_PER_FRAME: u32 = INSTRUCTIONS_PER_SECOND / 60;
 
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     v: [u8; 16],                // data registers V0-VF
     i: u16,                     // index register
     pc: u16,                    // program counter
-    stack: [u16; 12],           // subroutine stack
+    stack: Vec<u16>,            // subroutine stack, depth depends on the platform
     stack_ptr: usize,           // subroutine stack pointer
     delay_timer: u8,            // decrements at 60hz while > 0
     sound_timer: u8,            // decrements at 60hz while and play tone when > 0
-    instruction_map: HashMap<u16, fn(&mut Self)>,
-    current_inst: Instruction,
-    ram_handler: *mut ram::RAM,
-    display_handler: *mut display::Display,
-    keypad_handler: *mut [bool; 16]
+    current_addr: u16,          // address of the instruction being executed
+    quirks: Quirks,
+    wait_vblank: bool,          // set by DXYN when the display wait quirk is on
+    rpl: [u8; 16],              // SUPER-CHIP persistent user flags
+    exited: bool,               // 00FD was executed
+    fault_policy: FaultPolicy,
+    instructions_per_frame: u32,    // speed, instructions run in one 60hz frame
+    frame_cycles: u32,          // instructions executed in the current frame
+    cycles: u64,                // instructions executed since reset
+    reporting: bool,            // record register writes for watchpoints
+    accesses: Vec<Access>,
+    tracer: Option<Box<dyn TraceSink>>,
+    seed: u64,                  // seed the random generator started from
+    rng: Rng                    // CXNN random numbers
+}
+
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new(Quirks::default())
+    }
 }
 
 
 impl CPU {
-    pub fn new() -> Self {
+    /// Panics when the stack depth quirk is 0 or above [`MAX_STACK_DEPTH`].
+    pub fn new(quirks: Quirks) -> Self {
+        let seed: u64 = rand::random();
+
+        assert!(
+            (1..=MAX_STACK_DEPTH).contains(&quirks.stack_depth),
+            "stack depth must be 1 to {}, not {}", MAX_STACK_DEPTH, quirks.stack_depth
+        );
+
         Self {
             v: [0; 16],
             i: 0,
             pc: ENTRY_POINT,
-            stack: [0; 12],
+            stack: vec![0; quirks.stack_depth],
             stack_ptr: 0,
             delay_timer: 0,
             sound_timer: 0,
-            instruction_map: Self::create_instruction_map(),
-            current_inst: Instruction::new(),
-            ram_handler: std::ptr::null_mut(),
-            display_handler: std::ptr::null_mut(),
-            keypad_handler: std::ptr::null_mut()
+            current_addr: ENTRY_POINT,
+            quirks,
+            wait_vblank: false,
+            rpl: [0; 16],
+            exited: false,
+            fault_policy: FaultPolicy::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            frame_cycles: 0,
+            cycles: 0,
+            reporting: false,
+            accesses: Vec::new(),
+            tracer: None,
+            seed,
+            rng: Rng::new(Algorithm::default(), seed)
         }
     }
 
-    pub fn bind_ram(&mut self, ram: *mut ram::RAM) {
-        self.ram_handler = ram;
+    /// Restarts the CXNN random sequence from `seed` with the current algorithm.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(self.rng.algorithm().unwrap_or_default(), seed);
     }
 
-    pub fn bind_display(&mut self, display: *mut display::Display) {
-        self.display_handler = display;
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    pub fn bind_keypad(&mut self, keypad: *mut [bool; 16]) {
-        self.keypad_handler = keypad;
+    /// Replaces the CXNN generator, e.g. with another algorithm or a scripted sequence.
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
     }
 
-    pub fn make_cycle(&mut self) {
-        for _ in 0..INSTRUCTIONS_PER_SECOND / 60 {
-            self.exec_instruction();
-        }
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
 
-        self.update_timers();
+    pub fn fault_policy(&self) -> FaultPolicy {
+        self.fault_policy
     }
 
-    fn update_timers(&mut self) {
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-        }
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+    }
 
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
-            // TODO: play sound
-        }
-        else {
-            // TODO: stop playing sound
-        }
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
     }
 
-    fn exec_instruction(&mut self) {
-        if self.ram_handler.is_null() || self.display_handler.is_null() {
-            panic!("nullpointer exception");
-        }
+    /// Sets the speed, at least one instruction runs every frame.
+    pub fn set_instructions_per_frame(&mut self, instructions: u32) {
+        self.instructions_per_frame = instructions.max(1);
+    }
 
-        unsafe {
-            self.current_inst = Instruction::from(
-                (*self.ram_handler).read(self.pc as usize),
-                (*self.ram_handler).read(self.pc as usize + 1)
-            );
-
-            self.pc += 2;
-    
-            let category: u16 = (self.current_inst.opcode >> 12) & 0x0F;
-            
-            let exec: Option<&fn(&mut CPU)> = self
-                .instruction_map
-                .get(&category);
-
-            if let Some(e) = exec {
-                e(self);
-            } else {
-                panic!("unimplemented code");
-            }
-        }
+    /// True once the program executed the SUPER-CHIP exit instruction.
+    pub fn is_exited(&self) -> bool {
+        self.exited
     }
 
-    fn create_instruction_map() -> HashMap<u16, fn(&mut Self)> {
-        let arr: [(u16, fn(&mut Self)); 16] = [
-            (0x00, Self::x00),
-            (0x01, Self::x01),
-            (0x02, Self::x02),
-            (0x03, Self::x03),
-            (0x04, Self::x04),
-            (0x05, Self::x05),
-            (0x06, Self::x06),
-            (0x07, Self::x07),
-            (0x08, Self::x08),
-            (0x09, Self::x09),
-            (0x0A, Self::x0a),
-            (0x0B, Self::x0b),
-            (0x0C, Self::x0c),
-            (0x0D, Self::x0d),
-            (0x0E, Self::x0e),
-            (0x0F, Self::x0f)
-        ];
-
-        HashMap::from(arr)
-    }
-
-    fn x00(&mut self) {
-        match self.current_inst.nn {
-            0xE0 => unsafe {
-                (*self.display_handler).clear();
-            }
-            0xEE => {
-                self.stack_ptr -= 1;
-                self.pc = self.stack[self.stack_ptr];
-            }
-            _ => { println!("unimplemented code"); }
-        }
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
     }
 
-    fn x01(&mut self) {
-        self.pc = self.current_inst.nnn;
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
     }
 
-    fn x02(&mut self) {
-        self.stack[self.stack_ptr] = self.pc;
-        self.stack_ptr += 1;
-        self.pc = self.current_inst.nnn;
+    pub fn i(&self) -> u16 {
+        self.i
     }
 
-    fn x03(&mut self) {
-        if self.v[self.current_inst.x] == self.current_inst.nn {
-            self.pc += 2;
-        }
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_ptr]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Total number of instructions executed.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Address of the instruction executed last, or being executed.
+    pub fn current_addr(&self) -> u16 {
+        self.current_addr
+    }
+
+    /// Turns recording of V and I writes on or off, recorded writes are dropped.
+    pub fn set_reporting(&mut self, reporting: bool) {
+        self.reporting = reporting;
+        self.accesses.clear();
     }
 
-    fn x04(&mut self) {
-        if self.v[self.current_inst.x] != self.current_inst.nn {
-            self.pc += 2;
+    /// Register writes recorded since the last call.
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.accesses)
+    }
+
+    /// Sets the sink receiving every instruction before it executes, None stops tracing.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn TraceSink>>) {
+        self.tracer = tracer;
+    }
+
+    /// Writes the registers, stack and timers, quirks are saved separately.
+    pub fn save_state(&self, w: &mut Writer) {
+        w.bytes(&self.v);
+        w.u16(self.i);
+        w.u16(self.pc);
+        w.u16(self.stack.len() as u16);
+
+        for addr in self.stack.iter() {
+            w.u16(*addr);
         }
+
+        w.u16(self.stack_ptr as u16);
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.u16(self.current_addr);
+        w.bool(self.wait_vblank);
+        w.bytes(&self.rpl);
+        w.bool(self.exited);
+        w.u32(self.frame_cycles);
+        w.u64(self.cycles);
+        w.u64(self.seed);
+        self.rng.save_state(w);
     }
 
-    fn x05(&mut self) {
-        if self.current_inst.n != 0 {
-            println!("wrong opcode");
-            return;
+    /// Restores a state written by [`CPU::save_state`]. Nothing changes on error,
+    /// the fault policy and tracer are kept.
+    pub fn load_state(&mut self, r: &mut Reader, quirks: Quirks) -> Result<(), Chip8Error> {
+        let v: [u8; 16] = r.array()?;
+        let i: u16 = r.u16()?;
+        let pc: u16 = r.u16()?;
+        let stack_len: usize = r.u16()? as usize;
+        let stack: Vec<u16> = (0..stack_len).map(|_| r.u16()).collect::<Result<_, _>>()?;
+        let stack_ptr: usize = r.u16()? as usize;
+        let delay_timer: u8 = r.u8()?;
+        let sound_timer: u8 = r.u8()?;
+        let current_addr: u16 = r.u16()?;
+        let wait_vblank: bool = r.bool()?;
+        let rpl: [u8; 16] = r.array()?;
+        let exited: bool = r.bool()?;
+        let frame_cycles: u32 = r.u32()?;
+        let cycles: u64 = r.u64()?;
+        let seed: u64 = r.u64()?;
+        let rng: Rng = Rng::from_state(r)?;
+
+        if stack.len() != quirks.stack_depth {
+            return Err(Chip8Error::BadSaveState { reason: "stack does not match the stack depth quirk" });
         }
 
-        if self.v[self.current_inst.x] == self.v[self.current_inst.y] {
-            self.pc += 2;
+        if stack_ptr > stack.len() {
+            return Err(Chip8Error::BadSaveState { reason: "stack pointer is out of range" });
         }
-    }
 
-    fn x06(&mut self) {
-        self.v[self.current_inst.x] = self.current_inst.nn;
+        self.v = v;
+        self.i = i;
+        self.pc = pc;
+        self.stack = stack;
+        self.stack_ptr = stack_ptr;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.current_addr = current_addr;
+        self.quirks = quirks;
+        self.wait_vblank = wait_vblank;
+        self.rpl = rpl;
+        self.exited = exited;
+        self.frame_cycles = frame_cycles;
+        self.cycles = cycles;
+        self.seed = seed;
+        self.rng = rng;
+
+        Ok(())
     }
 
-    fn x07(&mut self) {
-        self.v[self.current_inst.x] = self.v[self.current_inst.x]
-            .wrapping_add(self.current_inst.nn); //+= self.current_inst.nn;
+    pub fn make_cycle(&mut self, bus: &mut Bus) -> Result<(), Chip8Error> {
+        while !self.step(bus)? {}
+
+        Ok(())
     }
 
-    fn x08(&mut self) {
-        match self.current_inst.n {
-            0x0 => {
-                self.v[self.current_inst.x] = self.v[self.current_inst.y];
-            }
-            0x1 => {
-                self.v[self.current_inst.x] |= self.v[self.current_inst.y];
-                self.v[0xF] = 0; // IDK maybe i should remove it
-            }
-            0x2 => {
-                self.v[self.current_inst.x] &= self.v[self.current_inst.y];
-                self.v[0xF] = 0; // IDK maybe i should remove it
-            }
-            0x3 => {
-                self.v[self.current_inst.x] ^= self.v[self.current_inst.y];
-                self.v[0xF] = 0; // IDK maybe i should remove it
-            }
-            0x4 => {
-                self.v[0xF] = (self.v[self.current_inst.x] as u16 + self.v[self.current_inst.y] as u16 > 255) as u8;
+    /// Executes a single instruction and returns true when it completed the frame,
+    /// in which case the timers have been ticked.
+    pub fn step(&mut self, bus: &mut Bus) -> Result<bool, Chip8Error> {
+        if !self.exited {
+            if let Err(err) = self.exec_instruction(bus) {
+                // a trap leaves pc on the faulting instruction so it can be inspected and retried
+                if self.fault_policy == FaultPolicy::Trap {
+                    self.pc = self.current_addr;
+                }
 
-                self.v[self.current_inst.x] = self.v[self.current_inst.x]
-                    .wrapping_add(self.v[self.current_inst.y]);
+                return Err(err);
             }
-            0x5 => {
-                self.v[0xF] = (self.v[self.current_inst.y] <= self.v[self.current_inst.x]) as u8;
 
-                self.v[self.current_inst.x] = self.v[self.current_inst.x]
-                    .wrapping_sub(self.v[self.current_inst.y]);
-            }
-            0x6 => {
-                self.v[0xF] = self.v[self.current_inst.x] & 0x1;
-                self.v[self.current_inst.x] >>= 1;
-            }
-            0x7 => {
-                self.v[0xF] = (self.v[self.current_inst.x] <= self.v[self.current_inst.y]) as u8;
+            self.frame_cycles += 1;
+            self.cycles += 1;
+        }
 
-                self.v[self.current_inst.x] = self.v[self.current_inst.y]
-                    .wrapping_sub(self.v[self.current_inst.x]);
-            }
-            0xE => {
-                self.v[0xF] = (self.v[self.current_inst.x] & 0x80) >> 7;
-                self.v[self.current_inst.x] <<= 1;
-            }
+        if self.exited || self.wait_vblank || self.frame_cycles >= self.instructions_per_frame {
+            self.frame_cycles = 0;
+            self.wait_vblank = false;
+            self.update_timers();
 
-            _ => {
-                println!("wrong opcode");
-            }
+            return Ok(true);
         }
 
-        
+        Ok(false)
     }
 
-    fn x09 (&mut self) {
-        if self.v[self.current_inst.x] != self.v[self.current_inst.y] {
-            self.pc += 2;
+    fn update_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
         }
-    }
 
-    fn x0a(&mut self) {
-        self.i = self.current_inst.nnn;
+        // the machine reports the buzzer state to its audio sink after every cycle
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
     }
 
-    fn x0b(&mut self) {
-        self.pc = self.v[0] as u16 + self.current_inst.nnn;
-    }
+    pub fn exec_instruction(&mut self, bus: &mut Bus) -> Result<(), Chip8Error> {
+        self.current_addr = self.pc;
+
+        let op: Op = self.fetch(bus, self.pc)?;
+
+        // the entry holds the state before the instruction, it is only recorded
+        // once the instruction ran so a trapped and retried one appears once
+        let entry: Option<TraceEntry> = match self.tracer {
+            Some(_) => Some(self.trace_entry(bus)?),
+            None => None
+        };
+
+        self.pc = self.pc.wrapping_add(op.size());
+        self.execute(op, bus)?;
 
-    fn x0c(&mut self) {
-        self.v[self.current_inst.x] = rand::random::<u8>() & self.current_inst.nn;
+        if let (Some(tracer), Some(entry)) = (self.tracer.as_mut(), entry) {
+            tracer.record(&entry)?;
+        }
+
+        Ok(())
     }
 
-    fn x0d(&mut self) {
-        let mut x_coord: u8 = self.v[self.current_inst.x as usize] % display::WIDTH;
-        let mut y_coord: u8 = self.v[self.current_inst.y as usize] % display::HEIGHT;
-        let origin_x_coord: u8 = x_coord;
+    /// Decodes the instruction at `addr` without executing it.
+    pub fn fetch(&self, bus: &Bus, addr: u16) -> Result<Op, Chip8Error> {
+        let opcode: u16 = self.read_word(bus, addr)?;
 
-        self.v[0xF] = 0;
+        let next: u16 = if opcode == 0xF000 {
+            self.read_word(bus, addr.wrapping_add(2))?
+        } else {
+            0
+        };
 
-        for i in 0..self.current_inst.n {
-            let sprite_data: u8 = unsafe{ (*self.ram_handler).read((self.i + i as u16) as usize) };
-            x_coord = origin_x_coord;
+        Ok(Op::decode(opcode, next))
+    }
 
-            for j in (0..8).rev() {
-                let mut pixel: bool = unsafe {
-                    (*self.display_handler)
-                        .read_pixel((y_coord as u16 * display::WIDTH as u16 + x_coord as u16) as usize)
-                };
+    fn trace_entry(&self, bus: &Bus) -> Result<TraceEntry, Chip8Error> {
+        let opcode: u16 = self.read_word(bus, self.pc)?;
+
+        Ok(TraceEntry {
+            cycle: self.cycles,
+            pc: self.pc,
+            opcode,
+            next: if opcode == 0xF000 { self.read_word(bus, self.pc.wrapping_add(2))? } else { 0 },
+            v: self.v,
+            i: self.i,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            sp: self.stack_ptr as u8
+        })
+    }
 
-                let sprite_bit: bool = (sprite_data & (1 << j)) != 0;
+    fn execute(&mut self, op: Op, bus: &mut Bus) -> Result<(), Chip8Error> {
+        match op {
+            Op::ClearScreen => {
+                bus.display.clear();
+            }
+            Op::Return => {
+                if self.stack_ptr == 0 {
+                    if self.fault_policy != FaultPolicy::Wrap {
+                        return Err(Chip8Error::StackUnderflow { addr: self.current_addr });
+                    }
 
-                if sprite_bit && pixel {
-                    self.v[0xF] = 1;
+                    self.stack_ptr = self.stack.len();
                 }
 
-                pixel ^= sprite_bit;
+                self.stack_ptr -= 1;
+                self.pc = self.stack[self.stack_ptr];
+            }
+            Op::ScrollDown { n } => {
+                bus.display.scroll_down(n as usize);
+            }
+            Op::ScrollUp { n } => {
+                bus.display.scroll_up(n as usize);
+            }
+            Op::ScrollRight => {
+                bus.display.scroll_right(4);
+            }
+            Op::ScrollLeft => {
+                bus.display.scroll_left(4);
+            }
+            Op::Exit => {
+                self.exited = true;
+            }
+            Op::Lores => {
+                bus.display.set_hires(false);
+            }
+            Op::Hires => {
+                bus.display.set_hires(true);
+            }
+            Op::Jump { nnn } => {
+                self.pc = nnn;
+            }
+            Op::Call { nnn } => {
+                if self.stack_ptr == self.stack.len() {
+                    if self.fault_policy != FaultPolicy::Wrap {
+                        return Err(Chip8Error::StackOverflow { addr: self.current_addr });
+                    }
 
-                unsafe {
-                    (*self.display_handler)
-                        .write_pixel((y_coord as u16 * display::WIDTH as u16 + x_coord as u16) as usize, pixel);
+                    self.stack_ptr = 0;
                 }
 
-                x_coord += 1;
-
-                if x_coord >= display::WIDTH {
-                    break
+                self.stack[self.stack_ptr] = self.pc;
+                self.stack_ptr += 1;
+                self.pc = nnn;
+            }
+            Op::SkipEqImm { x, nn } => {
+                if self.v[x as usize] == nn {
+                    self.skip(bus)?;
+                }
+            }
+            Op::SkipNeImm { x, nn } => {
+                if self.v[x as usize] != nn {
+                    self.skip(bus)?;
+                }
+            }
+            Op::SkipEqReg { x, y } => {
+                if self.v[x as usize] == self.v[y as usize] {
+                    self.skip(bus)?;
+                }
+            }
+            Op::SaveRange { x, y } => {
+                // XO-CHIP saves VX..VY in either direction without touching I
+                for (offset, reg) in Self::register_range(x, y).enumerate() {
+                    self.store(bus, self.i as usize + offset, self.v[reg])?;
+                }
+            }
+            Op::LoadRange { x, y } => {
+                for (offset, reg) in Self::register_range(x, y).enumerate() {
+                    let val: u8 = self.load(bus, self.i as usize + offset)?;
+                    self.set_v(reg as u8, val);
                 }
             }
+            Op::LoadImm { x, nn } => {
+                self.set_v(x, nn);
+            }
+            Op::AddImm { x, nn } => {
+                self.set_v(x, self.v[x as usize].wrapping_add(nn));
+            }
+            Op::Move { x, y } => {
+                self.set_v(x, self.v[y as usize]);
+            }
+            Op::Or { x, y } => {
+                self.set_v(x, self.v[x as usize] | self.v[y as usize]);
+                self.reset_flag();
+            }
+            Op::And { x, y } => {
+                self.set_v(x, self.v[x as usize] & self.v[y as usize]);
+                self.reset_flag();
+            }
+            Op::Xor { x, y } => {
+                self.set_v(x, self.v[x as usize] ^ self.v[y as usize]);
+                self.reset_flag();
+            }
+            // flag is always written after the result so VF as operand behaves like on hardware
+            Op::Add { x, y } => {
+                let (result, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
 
-            y_coord += 1;
+                self.set_v(x, result);
+                self.set_v(0xF, carry as u8);
+            }
+            Op::Sub { x, y } => {
+                let (result, borrow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
 
-            if y_coord >= display::HEIGHT {
-                break
+                self.set_v(x, result);
+                self.set_v(0xF, !borrow as u8);
             }
-        }
-    }
+            Op::ShiftRight { x, y } => {
+                let source: u8 = self.shift_source(x, y);
 
-    fn x0e(&mut self) {
-        if self.keypad_handler.is_null() {
-            return
-        }
+                self.set_v(x, source >> 1);
+                self.set_v(0xF, source & 0x1);
+            }
+            Op::SubReverse { x, y } => {
+                let (result, borrow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
 
-        match self.current_inst.nn {
-            0x9E => unsafe {
-                if (*self.keypad_handler)[self.v[self.current_inst.x] as usize] {
-                    self.pc += 2;
-                }
+                self.set_v(x, result);
+                self.set_v(0xF, !borrow as u8);
             }
-            0xA1 => unsafe {
-                if !(*self.keypad_handler)[self.v[self.current_inst.x] as usize] {
-                    self.pc += 2;
+            Op::ShiftLeft { x, y } => {
+                let source: u8 = self.shift_source(x, y);
+
+                self.set_v(x, source << 1);
+                self.set_v(0xF, (source & 0x80) >> 7);
+            }
+            Op::SkipNeReg { x, y } => {
+                if self.v[x as usize] != self.v[y as usize] {
+                    self.skip(bus)?;
                 }
             }
-            _ => ()
-        }
-    }
-
-    fn x0f(&mut self) {
-        match self.current_inst.nn {
-            0x0A => unsafe {
-                let mut any_key_pressed: bool = false;
+            Op::LoadIndex { nnn } => {
+                self.set_i(nnn);
+            }
+            Op::JumpOffset { x, nnn } => {
+                let offset: u8 = if self.quirks.jump_with_vx {
+                    self.v[x as usize]
+                } else {
+                    self.v[0]
+                };
 
-                for i in 0..(*self.keypad_handler).len() {
-                    if (*self.keypad_handler)[i] {
-                        self.v[self.current_inst.x] = i as u8;
-                        any_key_pressed = true;
+                self.pc = offset as u16 + nnn;
+            }
+            Op::Random { x, nn } => {
+                let random: u8 = self.rng.next_u8(bus.frame as u8);
+                self.set_v(x, random & nn);
+            }
+            Op::DrawSprite { x, y, n } => {
+                self.draw_sprite(bus, x, y, n)?;
+            }
+            Op::SkipKeyPressed { x } => {
+                if self.key_pressed(bus, x) {
+                    self.skip(bus)?;
+                }
+            }
+            Op::SkipKeyReleased { x } => {
+                if !self.key_pressed(bus, x) {
+                    self.skip(bus)?;
+                }
+            }
+            Op::LoadLongIndex { nnnn } => {
+                self.set_i(nnnn);
+            }
+            Op::SelectPlanes { n } => {
+                bus.display.select_planes(n);
+            }
+            Op::LoadAudio => {
+                let mut buffer: [u8; audio::PATTERN_SIZE] = [0; audio::PATTERN_SIZE];
 
-                        break;
-                    }
+                for (offset, byte) in buffer.iter_mut().enumerate() {
+                    *byte = self.load(bus, self.i as usize + offset)?;
                 }
 
-                if !any_key_pressed {
-                    self.pc -= 2;
+                bus.audio.load(buffer);
+            }
+            Op::GetDelay { x } => {
+                self.set_v(x, self.delay_timer);
+            }
+            Op::WaitKey { x } => {
+                match bus.keypad.iter().position(|pressed| *pressed) {
+                    Some(key) => {
+                        self.set_v(x, key as u8);
+                    }
+                    None => {
+                        self.pc = self.current_addr;
+                    }
                 }
             }
-            0x1E => {
-                self.i += self.v[self.current_inst.x] as u16;
+            Op::SetDelay { x } => {
+                self.delay_timer = self.v[x as usize];
             }
-            0x07 => {
-                self.v[self.current_inst.x] = self.delay_timer;
+            Op::SetSound { x } => {
+                self.sound_timer = self.v[x as usize];
             }
-            0x15 => {
-                self.delay_timer = self.v[self.current_inst.x];
+            Op::AddIndex { x } => {
+                self.set_i(self.i.wrapping_add(self.v[x as usize] as u16));
             }
-            0x18 => {
-                self.sound_timer = self.v[self.current_inst.x];
+            Op::FontChar { x } => {
+                let digit: u16 = (self.v[x as usize] & 0x0F) as u16;
+                self.set_i(font::FONT_ADDR as u16 + digit * 5);
             }
-            0x29 => {
-                self.i = self.v[self.current_inst.x].wrapping_mul(5) as u16;
+            Op::BigFontChar { x } => {
+                let digit: u16 = (self.v[x as usize] & 0x0F) as u16;
+                self.set_i(font::BIG_FONT_ADDR as u16 + digit * 10);
             }
-            0x33 => unsafe {
-                let mut bcd: u8 = self.v[self.current_inst.x];
+            Op::Bcd { x } => {
+                let mut bcd: u8 = self.v[x as usize];
 
-                (*self.ram_handler).write(self.i as usize + 2, bcd %10);
+                self.store(bus, self.i as usize + 2, bcd %10)?;
                 bcd /= 10;
-                (*self.ram_handler).write(self.i as usize + 1, bcd %10);
+                self.store(bus, self.i as usize + 1, bcd %10)?;
                 bcd /= 10;
-                (*self.ram_handler).write(self.i as usize, bcd);
+                self.store(bus, self.i as usize, bcd)?;
+            }
+            Op::SetPitch { x } => {
+                bus.audio.set_pitch(self.v[x as usize]);
             }
-            0x55 => unsafe {
-                for i in 0..self.current_inst.x + 1 {
-                    (*self.ram_handler).write(self.i as usize + i, self.v[i]);
+            Op::Store { x } => {
+                for i in 0..x as usize + 1 {
+                    self.store(bus, self.i as usize + i, self.v[i])?;
                 }
+
+                self.increment_index(x);
+            }
+            Op::Load { x } => {
+                for i in 0..x as usize + 1 {
+                    let val: u8 = self.load(bus, self.i as usize + i)?;
+                    self.set_v(i as u8, val);
+                }
+
+                self.increment_index(x);
+            }
+            Op::SaveFlags { x } => {
+                let count: usize = x as usize + 1;
+                self.rpl[..count].copy_from_slice(&self.v[..count]);
             }
-            0x65 => unsafe {
-                for i in 0..self.current_inst.x + 1 {
-                    self.v[i] = (*self.ram_handler).read(self.i as usize + i);
+            Op::LoadFlags { x } => {
+                for reg in 0..=x {
+                    self.set_v(reg, self.rpl[reg as usize]);
                 }
             }
-            _ => ()
+            Op::Sys { nnn: opcode } | Op::Unknown { opcode } => {
+                return Err(Chip8Error::UnknownOpcode { addr: self.current_addr, opcode });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_sprite(&mut self, bus: &mut Bus, x: u8, y: u8, n: u8) -> Result<(), Chip8Error> {
+        let width: u16 = bus.display.width() as u16;
+        let height: u16 = bus.display.height() as u16;
+        let origin_x: u16 = self.v[x as usize] as u16 % width;
+        let origin_y: u16 = self.v[y as usize] as u16 % height;
+
+        // DXY0 draws a 16x16 SUPER-CHIP sprite made of two bytes per row
+        let (rows, columns): (u16, u16) = match n {
+            0 => (16, 16),
+            n => (n as u16, 8)
+        };
+        let row_size: u16 = columns / 8;
+
+        self.set_v(0xF, 0);
+
+        // every selected XO-CHIP plane consumes its own sprite data, one after another
+        let mut sprite_addr: u16 = self.i;
+
+        for plane in (0..display::PLANES).map(|p| 1u8 << p) {
+            if bus.display.selected_planes() & plane == 0 {
+                continue;
+            }
+
+            for i in 0..rows {
+                let mut y_coord: u16 = origin_y + i;
+
+                if y_coord >= height {
+                    if !self.quirks.wrap {
+                        break
+                    }
+
+                    y_coord %= height;
+                }
+
+                let mut sprite_data: u16 = 0;
+
+                for byte in 0..row_size {
+                    let addr: usize = sprite_addr.wrapping_add(i * row_size + byte) as usize;
+                    sprite_data = (sprite_data << 8) | self.load(bus, addr)? as u16;
+                }
+
+                for j in 0..columns {
+                    let mut x_coord: u16 = origin_x + j;
+
+                    if x_coord >= width {
+                        if !self.quirks.wrap {
+                            break
+                        }
+
+                        x_coord %= width;
+                    }
+
+                    let sprite_bit: bool = (sprite_data & (1 << (columns - 1 - j))) != 0;
+                    let addr: usize = (y_coord * width + x_coord) as usize;
+                    let pixel: bool = bus.display.read_plane(addr, plane);
+
+                    if sprite_bit && pixel {
+                        self.set_v(0xF, 1);
+                    }
+
+                    bus.display.write_plane(addr, plane, pixel ^ sprite_bit);
+                }
+            }
+
+            sprite_addr = sprite_addr.wrapping_add(rows * row_size);
+        }
+
+        self.wait_vblank = self.quirks.display_wait;
+
+        Ok(())
+    }
+
+    fn set_v(&mut self, x: u8, val: u8) {
+        self.v[x as usize] = val;
+
+        if self.reporting {
+            self.accesses.push(Access {
+                location: Location::V(x),
+                kind: AccessKind::Write,
+                value: val as u16
+            });
+        }
+    }
+
+    fn set_i(&mut self, val: u16) {
+        self.i = val;
+
+        if self.reporting {
+            self.accesses.push(Access {
+                location: Location::I,
+                kind: AccessKind::Write,
+                value: val
+            });
+        }
+    }
+
+    fn reset_flag(&mut self) {
+        if self.quirks.vf_reset {
+            self.set_v(0xF, 0);
+        }
+    }
+
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift {
+            self.v[x as usize]
+        } else {
+            self.v[y as usize]
+        }
+    }
+
+    // only the low nibble selects a key
+    fn key_pressed(&self, bus: &Bus, x: u8) -> bool {
+        bus.keypad[(self.v[x as usize] & 0x0F) as usize]
+    }
+
+    fn increment_index(&mut self, x: u8) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::Unchanged => (),
+            MemoryIncrement::ByX => {
+                self.set_i(self.i.wrapping_add(x as u16));
+            }
+            MemoryIncrement::ByXPlusOne => {
+                self.set_i(self.i.wrapping_add(x as u16 + 1));
+            }
+        }
+    }
+
+    // skips the next instruction, which is two words long for XO-CHIP F000 NNNN
+    fn skip(&mut self, bus: &mut Bus) -> Result<(), Chip8Error> {
+        let next: u16 = self.read_word(bus, self.pc)?;
+
+        self.pc = self.pc.wrapping_add(if next == 0xF000 { 4 } else { 2 });
+
+        Ok(())
+    }
+
+    // instruction words are fetched without reporting a memory read
+    fn read_word(&self, bus: &Bus, addr: u16) -> Result<u16, Chip8Error> {
+        let high: u16 = bus.ram.peek(self.map_addr(bus, addr as usize))? as u16;
+        let low: u16 = bus.ram.peek(self.map_addr(bus, addr as usize + 1))? as u16;
+
+        Ok((high << 8) | low)
+    }
+
+    fn load(&self, bus: &Bus, addr: usize) -> Result<u8, Chip8Error> {
+        bus.ram.read(self.map_addr(bus, addr))
+    }
+
+    fn store(&self, bus: &mut Bus, addr: usize, val: u8) -> Result<(), Chip8Error> {
+        bus.ram.write(self.map_addr(bus, addr), val)
+    }
+
+    // with the wrap policy out of range addresses fold back into memory like the address bus would
+    fn map_addr(&self, bus: &Bus, addr: usize) -> usize {
+        match self.fault_policy {
+            FaultPolicy::Wrap => addr % bus.ram.size(),
+            _ => addr
+        }
+    }
+
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y): (usize, usize) = (x as usize, y as usize);
+
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
         }
     }
 }
@@ -0,0 +1,168 @@
+use super::super::error::Chip8Error;
+use super::super::savestate::{
+    Reader,
+    Writer
+};
+
+/// Deepest supported stack, traces report the stack pointer as a byte.
+pub const MAX_STACK_DEPTH: usize = 255;
+
+/// Names accepted by [`Quirks::set`].
+pub const NAMES: [&str; 7] = [
+    "shift", "vf-reset", "memory-increment", "jump-with-vx", "wrap", "display-wait", "stack-depth"
+];
+
+/// How FX55/FX65 change the index register after a memory transfer.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MemoryIncrement {
+    Unchanged,      // I stays where it was (SUPER-CHIP)
+    ByX,            // I += X (CHIP-48)
+    ByXPlusOne      // I += X + 1 (COSMAC VIP, XO-CHIP)
+}
+
+
+/// Behaviours that differ between CHIP-8 interpreters.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Quirks {
+    pub shift: bool,                        // 8XY6/8XYE shift VX in place instead of VY
+    pub vf_reset: bool,                     // 8XY1/8XY2/8XY3 reset VF to zero
+    pub memory_increment: MemoryIncrement,  // I after FX55/FX65
+    pub jump_with_vx: bool,                 // BXNN jumps to XNN + VX instead of NNN + V0
+    pub wrap: bool,                         // DXYN wraps sprites around the edges instead of clipping
+    pub display_wait: bool,                 // DXYN waits for the next 60hz frame
+    pub stack_depth: usize                  // nested 2NNN calls before the stack overflows, 1 to MAX_STACK_DEPTH
+}
+
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
+
+
+impl Quirks {
+    /// Original interpreter of the RCA COSMAC VIP.
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift: false,
+            vf_reset: true,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump_with_vx: false,
+            wrap: false,
+            display_wait: true,
+            stack_depth: 12
+        }
+    }
+
+    /// CHIP-48 for the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Self {
+            shift: true,
+            vf_reset: false,
+            memory_increment: MemoryIncrement::ByX,
+            jump_with_vx: true,
+            wrap: false,
+            display_wait: false,
+            stack_depth: 16
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn superchip() -> Self {
+        Self {
+            shift: true,
+            vf_reset: false,
+            memory_increment: MemoryIncrement::Unchanged,
+            jump_with_vx: true,
+            wrap: false,
+            display_wait: false,
+            stack_depth: 16
+        }
+    }
+
+    /// Octo and most modern interpreters, also used by XO-CHIP.
+    pub fn octo() -> Self {
+        Self {
+            shift: false,
+            vf_reset: false,
+            memory_increment: MemoryIncrement::ByXPlusOne,
+            jump_with_vx: false,
+            wrap: true,
+            display_wait: false,
+            stack_depth: 16
+        }
+    }
+
+    /// Changes one quirk by the name used in movies, e.g. `wrap=1` or `memory-increment=x`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let flag = || match value {
+            "0" | "false" => Ok(false),
+            "1" | "true" => Ok(true),
+            _ => Err(format!("quirk {} must be 0 or 1, not '{}'", name, value))
+        };
+
+        match name {
+            "shift" => self.shift = flag()?,
+            "vf-reset" => self.vf_reset = flag()?,
+            "jump-with-vx" => self.jump_with_vx = flag()?,
+            "wrap" => self.wrap = flag()?,
+            "display-wait" => self.display_wait = flag()?,
+            "memory-increment" => {
+                self.memory_increment = match value {
+                    "unchanged" => MemoryIncrement::Unchanged,
+                    "x" => MemoryIncrement::ByX,
+                    "x+1" => MemoryIncrement::ByXPlusOne,
+                    _ => return Err(format!("memory-increment must be unchanged, x or x+1, not '{}'", value))
+                };
+            }
+            "stack-depth" => {
+                self.stack_depth = value
+                    .parse()
+                    .ok()
+                    .filter(|depth| (1..=MAX_STACK_DEPTH).contains(depth))
+                    .ok_or(format!("stack depth must be 1 to {}, not '{}'", MAX_STACK_DEPTH, value))?;
+            }
+            _ => return Err(format!("unknown quirk '{}'", name))
+        }
+
+        Ok(())
+    }
+
+    pub fn save_state(&self, w: &mut Writer) {
+        w.bool(self.shift);
+        w.bool(self.vf_reset);
+        w.u8(match self.memory_increment {
+            MemoryIncrement::Unchanged => 0,
+            MemoryIncrement::ByX => 1,
+            MemoryIncrement::ByXPlusOne => 2
+        });
+        w.bool(self.jump_with_vx);
+        w.bool(self.wrap);
+        w.bool(self.display_wait);
+        w.u16(self.stack_depth as u16);
+    }
+
+    pub fn from_state(r: &mut Reader) -> Result<Self, Chip8Error> {
+        let quirks: Quirks = Self {
+            shift: r.bool()?,
+            vf_reset: r.bool()?,
+            memory_increment: match r.u8()? {
+                0 => MemoryIncrement::Unchanged,
+                1 => MemoryIncrement::ByX,
+                2 => MemoryIncrement::ByXPlusOne,
+                _ => return Err(Chip8Error::BadSaveState { reason: "unknown memory increment quirk" })
+            },
+            jump_with_vx: r.bool()?,
+            wrap: r.bool()?,
+            display_wait: r.bool()?,
+            stack_depth: r.u16()? as usize
+        };
+
+        if !(1..=MAX_STACK_DEPTH).contains(&quirks.stack_depth) {
+            return Err(Chip8Error::BadSaveState { reason: "stack depth is out of range" });
+        }
+
+        Ok(quirks)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    fmt,
+    str::FromStr
+};
+use super::super::error::Chip8Error;
+use super::super::ram::font;
+use super::super::savestate::{
+    Reader,
+    Writer
+};
+
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Algorithm {
+    #[default]
+    Xorshift,       // well distributed 64-bit xorshift*
+    CosmacVip       // additive 8-bit generator in the style of the VIP interpreter
+}
+
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "xorshift" => Ok(Algorithm::Xorshift),
+            "vip" | "cosmac-vip" => Ok(Algorithm::CosmacVip),
+            _ => Err(format!("unknown random generator '{}', expected xorshift or vip", name))
+        }
+    }
+}
+
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::Xorshift => write!(f, "xorshift"),
+            Algorithm::CosmacVip => write!(f, "vip")
+        }
+    }
+}
+
+
+/// Generator behind CXNN, a field of the CPU so a run can be reproduced.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Rng {
+    Xorshift { state: u64 },
+    // the VIP adds bytes of its own interpreter code at a moving pointer to the
+    // previous value, mixed with the frame counter; the font stands in for that code
+    CosmacVip { value: u8, pointer: u8 },
+    // fixed values returned in order and repeated, for tests
+    Scripted { values: Vec<u8>, position: usize }
+}
+
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(Algorithm::default(), 0)
+    }
+}
+
+
+impl Rng {
+    pub fn new(algorithm: Algorithm, seed: u64) -> Self {
+        match algorithm {
+            Algorithm::Xorshift => {
+                // splitmix64 spreads small seeds over the whole state
+                let mut z: u64 = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+
+                // xorshift gets stuck on zero
+                Rng::Xorshift { state: if z == 0 { 1 } else { z } }
+            }
+            Algorithm::CosmacVip => Rng::CosmacVip {
+                value: seed as u8,
+                pointer: (seed >> 8) as u8
+            }
+        }
+    }
+
+    /// Returns `values` in order, starting over after the last one.
+    pub fn scripted(values: Vec<u8>) -> Self {
+        Rng::Scripted { values, position: 0 }
+    }
+
+    /// None for scripted sequences.
+    pub fn algorithm(&self) -> Option<Algorithm> {
+        match self {
+            Rng::Xorshift { .. } => Some(Algorithm::Xorshift),
+            Rng::CosmacVip { .. } => Some(Algorithm::CosmacVip),
+            Rng::Scripted { .. } => None
+        }
+    }
+
+    /// Next random byte, `frame` only matters to the VIP generator.
+    pub fn next_u8(&mut self, frame: u8) -> u8 {
+        match self {
+            Rng::Xorshift { state } => {
+                *state ^= *state >> 12;
+                *state ^= *state << 25;
+                *state ^= *state >> 27;
+
+                (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+            }
+            Rng::CosmacVip { value, pointer } => {
+                let page: usize = font::FONT_SET.len() + font::BIG_FONT_SET.len();
+                let code: u8 = match *pointer as usize % page {
+                    at if at < font::FONT_SET.len() => font::FONT_SET[at],
+                    at => font::BIG_FONT_SET[at - font::FONT_SET.len()]
+                };
+
+                *pointer = pointer.wrapping_add(1);
+                *value = value.wrapping_add(code).wrapping_add(frame);
+
+                *value
+            }
+            Rng::Scripted { values, position } => {
+                let Some(value) = values.get(*position % values.len().max(1)).copied() else {
+                    return 0;
+                };
+
+                *position += 1;
+
+                value
+            }
+        }
+    }
+
+    pub fn save_state(&self, w: &mut Writer) {
+        match self {
+            Rng::Xorshift { state } => {
+                w.u8(0);
+                w.u64(*state);
+            }
+            Rng::CosmacVip { value, pointer } => {
+                w.u8(1);
+                w.u8(*value);
+                w.u8(*pointer);
+            }
+            Rng::Scripted { values, position } => {
+                w.u8(2);
+                w.bytes(values);
+                w.u64(*position as u64);
+            }
+        }
+    }
+
+    pub fn from_state(r: &mut Reader) -> Result<Self, Chip8Error> {
+        match r.u8()? {
+            0 => Ok(Rng::Xorshift { state: r.u64()?.max(1) }),
+            1 => Ok(Rng::CosmacVip { value: r.u8()?, pointer: r.u8()? }),
+            2 => Ok(Rng::Scripted { values: r.bytes()?.to_vec(), position: r.u64()? as usize }),
+            _ => Err(Chip8Error::BadSaveState { reason: "unknown random generator" })
+        }
+    }
+}
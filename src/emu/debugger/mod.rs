@@ -0,0 +1,285 @@
+//! Breakpoints, watchpoints and stepping for a [`Machine`].
+//!
+//! Every run method executes at least one instruction, so resuming from a
+//! breakpoint does not stop on it again. Breakpoints are checked before the
+//! instruction at the new program counter runs, watchpoints right after the
+//! instruction that made the access.
+
+pub mod repl;
+pub mod watch;
+
+use std::{
+    collections::BTreeSet,
+    fmt
+};
+use super::Machine;
+use super::cpu::instruction::Op;
+use super::error::Chip8Error;
+use watch::{
+    Access,
+    Watch
+};
+
+
+/// Why a run method returned.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Stop {
+    Step,                       // the requested instruction or call completed
+    Breakpoint { addr: u16 },
+    Watchpoint { addr: u16, access: Access },   // instruction at `addr` made the access
+    Frame { frame: u64 },       // the requested frame was reached
+    Exited                      // the program executed 00FD
+}
+
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Step => write!(f, "step"),
+            Stop::Breakpoint { addr } => write!(f, "breakpoint at 0x{:04X}", addr),
+            Stop::Watchpoint { addr, access } => write!(f, "watchpoint: {} by 0x{:04X}", access, addr),
+            Stop::Frame { frame } => write!(f, "frame {}", frame),
+            Stop::Exited => write!(f, "program exited")
+        }
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watches: Vec<Watch>
+}
+
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns false when the breakpoint was already set.
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    /// Returns false when there was no breakpoint at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn is_breakpoint(&self, addr: u16) -> bool {
+        self.breakpoints.contains(&addr)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Returns false when the same watchpoint was already set.
+    pub fn add_watch(&mut self, watch: Watch) -> bool {
+        if self.watches.contains(&watch) {
+            return false;
+        }
+
+        self.watches.push(watch);
+        true
+    }
+
+    /// Removes the watchpoint at `index` of [`Debugger::watches`].
+    pub fn remove_watch(&mut self, index: usize) -> Option<Watch> {
+        (index < self.watches.len()).then(|| self.watches.remove(index))
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    fn triggered(&self, accesses: &[Access]) -> Option<Access> {
+        accesses
+            .iter()
+            .find(|access| self.watches.iter().any(|watch| watch.matches(access)))
+            .copied()
+    }
+}
+
+
+/// Copy of the CPU state shown by the debugger.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: usize,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub frame: u64
+}
+
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "PC 0x{:04X}  I 0x{:04X}  SP {}  DT {:02X}  ST {:02X}  frame {}",
+            self.pc, self.i, self.sp, self.delay_timer, self.sound_timer, self.frame
+        )?;
+
+        for row in self.v.chunks(8).enumerate() {
+            let (index, values) = row;
+            let registers: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(reg, value)| format!("V{:X} {:02X}", index * 8 + reg, value))
+                .collect();
+
+            writeln!(f, "{}", registers.join("  "))?;
+        }
+
+        let stack: Vec<String> = self.stack
+            .iter()
+            .map(|addr| format!("0x{:04X}", addr))
+            .collect();
+
+        write!(f, "stack [{}]", stack.join(" "))
+    }
+}
+
+
+impl Machine {
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: *self.cpu.v(),
+            i: self.cpu.i(),
+            pc: self.cpu.pc(),
+            sp: self.cpu.stack().len(),
+            stack: self.cpu.stack().to_vec(),
+            delay_timer: self.cpu.delay_timer(),
+            sound_timer: self.cpu.sound_timer(),
+            frame: self.frame
+        }
+    }
+
+    /// Decodes the instruction at `addr`, None when it lies outside of memory.
+    pub fn op_at(&self, addr: u16) -> Option<Op> {
+        let word = |addr: usize| -> Option<u16> {
+            Some((self.ram.peek(addr).ok()? as u16) << 8 | self.ram.peek(addr + 1).ok()? as u16)
+        };
+
+        let opcode: u16 = word(addr as usize)?;
+        let next: u16 = word(addr as usize + 2).unwrap_or(0);
+
+        Some(Op::decode(opcode, next))
+    }
+
+    /// Executes one instruction.
+    pub fn step(&mut self) -> Result<Stop, Chip8Error> {
+        self.run_until(|_| Some(Stop::Step))
+    }
+
+    /// Executes one instruction, running a `2NNN` call until it returns.
+    pub fn step_over(&mut self) -> Result<Stop, Chip8Error> {
+        let pc: u16 = self.cpu.pc();
+
+        match self.op_at(pc) {
+            Some(op @ Op::Call { .. }) => {
+                let depth: usize = self.cpu.stack().len();
+                let next: u16 = pc.wrapping_add(op.size());
+
+                self.run_until(|machine| {
+                    (machine.cpu.pc() == next && machine.cpu.stack().len() == depth).then_some(Stop::Step)
+                })
+            }
+            _ => self.step()
+        }
+    }
+
+    /// Runs until the current subroutine returns, or a single step outside of one.
+    pub fn step_out(&mut self) -> Result<Stop, Chip8Error> {
+        let depth: usize = self.cpu.stack().len();
+
+        if depth == 0 {
+            return self.step();
+        }
+
+        self.run_until(|machine| (machine.cpu.stack().len() < depth).then_some(Stop::Step))
+    }
+
+    /// Runs until `frame` frames have completed.
+    pub fn run_to_frame(&mut self, frame: u64) -> Result<Stop, Chip8Error> {
+        if self.frame >= frame {
+            return Ok(Stop::Frame { frame: self.frame });
+        }
+
+        self.run_until(|machine| (machine.frame >= frame).then_some(Stop::Frame { frame }))
+    }
+
+    /// Runs until the end of the current frame.
+    pub fn run_frame(&mut self) -> Result<Stop, Chip8Error> {
+        let frame: u64 = self.frame + 1;
+        self.run_until(|machine| (machine.frame >= frame).then_some(Stop::Frame { frame }))
+    }
+
+    /// Runs until a breakpoint is hit, the program exits or `frames` more frames have completed.
+    pub fn resume(&mut self, frames: u64) -> Result<Stop, Chip8Error> {
+        let frame: u64 = self.frame.saturating_add(frames.max(1));
+
+        self.run_until(|machine| (machine.frame >= frame).then_some(Stop::Frame { frame }))
+    }
+
+    fn run_until<F>(&mut self, done: F) -> Result<Stop, Chip8Error>
+    where
+        F: FnMut(&Machine) -> Option<Stop>
+    {
+        // accesses are only recorded while watchpoints are set
+        let reporting: bool = !self.debugger.watches.is_empty();
+
+        self.ram.set_reporting(reporting);
+        self.cpu.set_reporting(reporting);
+
+        let result: Result<Stop, Chip8Error> = self.run_watched(done);
+
+        self.ram.set_reporting(false);
+        self.cpu.set_reporting(false);
+
+        result
+    }
+
+    fn run_watched<F>(&mut self, mut done: F) -> Result<Stop, Chip8Error>
+    where
+        F: FnMut(&Machine) -> Option<Stop>
+    {
+        loop {
+            if self.cpu.is_exited() {
+                return Ok(Stop::Exited);
+            }
+
+            self.step_instruction()?;
+
+            let mut accesses: Vec<Access> = self.ram.take_accesses();
+            accesses.append(&mut self.cpu.take_accesses());
+
+            if let Some(access) = self.debugger.triggered(&accesses) {
+                return Ok(Stop::Watchpoint { addr: self.cpu.current_addr(), access });
+            }
+
+            let pc: u16 = self.cpu.pc();
+
+            if self.debugger.is_breakpoint(pc) {
+                return Ok(Stop::Breakpoint { addr: pc });
+            }
+
+            if let Some(stop) = done(self) {
+                return Ok(stop);
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::io::{
+    self,
+    BufRead,
+    Write
+};
+use super::Stop;
+use super::watch::{
+    Watch,
+    WatchKind
+};
+use super::super::Machine;
+use super::super::asm::SymbolMap;
+use super::super::error::Chip8Error;
+use super::super::trace::{
+    BinaryTrace,
+    TextTrace,
+    TraceSink
+};
+
+const PROMPT: &str = "(chip8) ";
+const CONTINUE_FRAMES: u64 = 60 * 60;  // a minute of emulated time
+const HELP: &str = "\
+break [addr]        set a breakpoint, list breakpoints without an address
+delete <addr|all>   remove breakpoints
+watch [target] [rw] watch addr, addr-end, vX or i, r/w/rw for memory (default w)
+unwatch <n|all>     remove watchpoints by number
+step [n]            execute n instructions
+next                step over a call
+finish              run until the current subroutine returns
+frame <n>           run until frame n
+continue [frames]   run until a breakpoint, exit or n frames (default 3600)
+regs                show registers, timers and stack
+mem <addr> [len]    dump memory
+dis [addr] [n]      disassemble n instructions
+key <k> <up|down>   release or press a keypad key
+screen              print the display
+trace <file> [bin]  log executed instructions, 'trace off' stops
+quit                leave the debugger
+addresses are hex or label names";
+
+
+/// Reads debugger commands from `input` until it ends or `quit` is entered.
+///
+/// An empty line repeats the previous command.
+pub fn run<R: BufRead, W: Write>(
+    machine: &mut Machine,
+    symbols: &SymbolMap,
+    input: R,
+    mut output: W
+) -> io::Result<()> {
+    let mut lines = input.lines();
+    let mut previous: String = String::new();
+
+    writeln!(output, "{}", location(machine, symbols, machine.cpu().pc()))?;
+
+    loop {
+        write!(output, "{}", PROMPT)?;
+        output.flush()?;
+
+        let Some(line) = lines.next() else {
+            return Ok(());
+        };
+
+        let line: String = line?;
+
+        if !line.trim().is_empty() {
+            previous = line;
+        }
+
+        let words: Vec<&str> = previous.split_whitespace().collect();
+
+        if matches!(words.first(), Some(&"quit") | Some(&"q")) {
+            return Ok(());
+        }
+
+        if let Err(message) = command(machine, symbols, &words, &mut output)? {
+            writeln!(output, "{}", message)?;
+        }
+    }
+}
+
+
+// outer error is the output failing, inner one a bad command
+fn command<W: Write>(
+    machine: &mut Machine,
+    symbols: &SymbolMap,
+    words: &[&str],
+    output: &mut W
+) -> io::Result<Result<(), String>> {
+    let result: Result<Stop, Chip8Error> = match words {
+        [] => return Ok(Ok(())),
+        ["help" | "h"] => {
+            writeln!(output, "{}", HELP)?;
+            return Ok(Ok(()));
+        }
+        ["break" | "b"] => {
+            for addr in machine.debugger().breakpoints() {
+                writeln!(output, "{}", location(machine, symbols, addr))?;
+            }
+
+            return Ok(Ok(()));
+        }
+        ["break" | "b", addr] => {
+            let Some(addr) = parse_addr(symbols, addr) else {
+                return Ok(Err(format!("bad address '{}'", addr)));
+            };
+
+            machine.debugger_mut().add_breakpoint(addr);
+            return Ok(Ok(()));
+        }
+        ["delete" | "d", "all"] => {
+            machine.debugger_mut().clear_breakpoints();
+            return Ok(Ok(()));
+        }
+        ["delete" | "d", addr] => {
+            let removed: bool = parse_addr(symbols, addr)
+                .is_some_and(|addr| machine.debugger_mut().remove_breakpoint(addr));
+
+            if !removed {
+                return Ok(Err(format!("no breakpoint at '{}'", addr)));
+            }
+
+            return Ok(Ok(()));
+        }
+        ["watch" | "w"] => {
+            for (index, watch) in machine.debugger().watches().iter().enumerate() {
+                writeln!(output, "{}: {}", index, watch)?;
+            }
+
+            return Ok(Ok(()));
+        }
+        ["watch" | "w", target, rest @ ..] => {
+            let kind: WatchKind = match rest {
+                [] | ["w"] => WatchKind::Write,
+                ["r"] => WatchKind::Read,
+                ["rw"] => WatchKind::ReadWrite,
+                _ => return Ok(Err(String::from("usage: watch <addr[-end]|vX|i> [r|w|rw]")))
+            };
+
+            let Some(watch) = parse_watch(symbols, target, kind) else {
+                return Ok(Err(format!("bad watch target '{}'", target)));
+            };
+
+            machine.debugger_mut().add_watch(watch);
+            return Ok(Ok(()));
+        }
+        ["unwatch", "all"] => {
+            machine.debugger_mut().clear_watches();
+            return Ok(Ok(()));
+        }
+        ["unwatch", index] => {
+            let removed: bool = index
+                .parse::<usize>()
+                .is_ok_and(|index| machine.debugger_mut().remove_watch(index).is_some());
+
+            if !removed {
+                return Ok(Err(format!("no watchpoint '{}'", index)));
+            }
+
+            return Ok(Ok(()));
+        }
+        ["step" | "s"] => machine.step(),
+        ["step" | "s", count] => {
+            let Ok(count) = count.parse::<u32>() else {
+                return Ok(Err(format!("bad count '{}'", count)));
+            };
+
+            let mut result: Result<Stop, Chip8Error> = Ok(Stop::Step);
+
+            for _ in 0..count {
+                result = machine.step();
+
+                if !matches!(result, Ok(Stop::Step)) {
+                    break;
+                }
+            }
+
+            result
+        }
+        ["next" | "n"] => machine.step_over(),
+        ["finish" | "out"] => machine.step_out(),
+        ["frame" | "f", frame] => {
+            let Ok(frame) = frame.parse::<u64>() else {
+                return Ok(Err(format!("bad frame '{}'", frame)));
+            };
+
+            machine.run_to_frame(frame)
+        }
+        ["continue" | "c"] => machine.resume(CONTINUE_FRAMES),
+        ["continue" | "c", frames] => {
+            let Ok(frames) = frames.parse::<u64>() else {
+                return Ok(Err(format!("bad frame count '{}'", frames)));
+            };
+
+            machine.resume(frames)
+        }
+        ["regs" | "r"] | ["stack"] => {
+            writeln!(output, "{}", machine.registers())?;
+            return Ok(Ok(()));
+        }
+        ["mem" | "x", addr, rest @ ..] => {
+            let Some(addr) = parse_addr(symbols, addr) else {
+                return Ok(Err(format!("bad address '{}'", addr)));
+            };
+
+            let len: usize = match rest {
+                [] => 16,
+                [len] => match len.parse::<usize>() {
+                    Ok(len) => len,
+                    Err(_) => return Ok(Err(format!("bad length '{}'", len)))
+                },
+                _ => return Ok(Err(String::from("usage: mem <addr> [len]")))
+            };
+
+            dump_memory(machine, addr, len, output)?;
+            return Ok(Ok(()));
+        }
+        ["dis", rest @ ..] => {
+            let addr: Option<u16> = match rest.first() {
+                Some(addr) => parse_addr(symbols, addr),
+                None => Some(machine.cpu().pc())
+            };
+
+            let count: Option<usize> = match rest.get(1) {
+                Some(count) => count.parse::<usize>().ok(),
+                None => Some(8)
+            };
+
+            let (Some(mut addr), Some(count)) = (addr, count) else {
+                return Ok(Err(String::from("usage: dis [addr] [n]")));
+            };
+
+            for _ in 0..count {
+                let Some(op) = machine.op_at(addr) else {
+                    break;
+                };
+
+                writeln!(output, "{}", location(machine, symbols, addr))?;
+                addr = addr.wrapping_add(op.size());
+            }
+
+            return Ok(Ok(()));
+        }
+        ["key", key, state @ ("up" | "down")] => {
+            let Some(key) = usize::from_str_radix(key, 16).ok().filter(|key| *key < 16) else {
+                return Ok(Err(format!("bad key '{}'", key)));
+            };
+
+            machine.set_key(key, *state == "down");
+            return Ok(Ok(()));
+        }
+        ["trace", "off"] => {
+            machine.set_tracer(None);
+            return Ok(Ok(()));
+        }
+        ["trace", path, rest @ ..] => {
+            let tracer: io::Result<Box<dyn TraceSink>> = match rest {
+                [] => TextTrace::create(path).map(|trace| Box::new(trace) as Box<dyn TraceSink>),
+                ["bin"] => BinaryTrace::create(path).map(|trace| Box::new(trace) as Box<dyn TraceSink>),
+                _ => return Ok(Err(String::from("usage: trace <file> [bin]")))
+            };
+
+            match tracer {
+                Ok(tracer) => machine.set_tracer(Some(tracer)),
+                Err(err) => return Ok(Err(format!("failed to create {}: {}", path, err)))
+            }
+
+            return Ok(Ok(()));
+        }
+        ["screen"] => {
+            let display = machine.display();
+
+            for row in display.pixels().chunks(display.width()) {
+                let line: String = row
+                    .iter()
+                    .map(|pixel| if *pixel == 0 { '.' } else { char::from(b'0' + pixel) })
+                    .collect();
+
+                writeln!(output, "{}", line)?;
+            }
+
+            return Ok(Ok(()));
+        }
+        _ => return Ok(Err(format!("unknown command '{}', try 'help'", words.join(" "))))
+    };
+
+    match result {
+        Ok(Stop::Step) => (),
+        Ok(stop) => writeln!(output, "{}", stop)?,
+        Err(err) => writeln!(output, "error: {}", err)?
+    }
+
+    writeln!(output, "{}", location(machine, symbols, machine.cpu().pc()))?;
+
+    Ok(Ok(()))
+}
+
+
+fn parse_addr(symbols: &SymbolMap, text: &str) -> Option<u16> {
+    symbols
+        .get(text)
+        .or_else(|| u16::from_str_radix(text.trim_start_matches("0x"), 16).ok())
+}
+
+
+fn parse_watch(symbols: &SymbolMap, text: &str, kind: WatchKind) -> Option<Watch> {
+    let lower: String = text.to_lowercase();
+
+    if lower == "i" {
+        return Some(Watch::Index);
+    }
+
+    if let Some(reg) = lower.strip_prefix('v').filter(|reg| reg.len() == 1) {
+        return u8::from_str_radix(reg, 16).ok().map(|x| Watch::Register { x });
+    }
+
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (parse_addr(symbols, start)?, parse_addr(symbols, end)?),
+        None => {
+            let addr: u16 = parse_addr(symbols, text)?;
+            (addr, addr)
+        }
+    };
+
+    (start <= end).then_some(Watch::Memory { start: start as usize, end: end as usize, kind })
+}
+
+
+fn location(machine: &Machine, symbols: &SymbolMap, addr: u16) -> String {
+    let label: String = symbols
+        .name_of(addr)
+        .map(|name| format!(" <{}>", name))
+        .unwrap_or_default();
+
+    match machine.op_at(addr) {
+        Some(op) => format!("0x{:04X}{}  {}", addr, label, op),
+        None => format!("0x{:04X}{}  <out of memory>", addr, label)
+    }
+}
+
+
+fn dump_memory<W: Write>(machine: &Machine, addr: u16, len: usize, output: &mut W) -> io::Result<()> {
+    let space: &[u8] = machine.ram().space();
+    let begin: usize = (addr as usize).min(space.len());
+    let end: usize = (begin + len).min(space.len());
+
+    for (row, bytes) in space[begin..end].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        writeln!(output, "0x{:04X}  {}", begin + row * 16, hex.join(" "))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AccessKind {
+    Read,
+    Write
+}
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Location {
+    Memory(usize),
+    V(u8),
+    I
+}
+
+
+/// Memory or register access reported by the CPU or RAM while watchpoints are set.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Access {
+    pub location: Location,
+    pub kind: AccessKind,
+    pub value: u16          // value read or written
+}
+
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind: &str = match self.kind {
+            AccessKind::Read => "read",
+            AccessKind::Write => "write"
+        };
+
+        match self.location {
+            Location::Memory(addr) => write!(f, "{} 0x{:02X} at 0x{:04X}", kind, self.value, addr),
+            Location::V(x) => write!(f, "{} 0x{:02X} to V{:X}", kind, self.value, x),
+            Location::I => write!(f, "{} 0x{:04X} to I", kind, self.value)
+        }
+    }
+}
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite
+}
+
+
+impl WatchKind {
+    fn matches(&self, kind: AccessKind) -> bool {
+        matches!(
+            (self, kind),
+            (WatchKind::ReadWrite, _) | (WatchKind::Read, AccessKind::Read) | (WatchKind::Write, AccessKind::Write)
+        )
+    }
+}
+
+
+/// Watchpoint, registers only report writes.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Watch {
+    Memory { start: usize, end: usize, kind: WatchKind },      // inclusive range
+    Register { x: u8 },
+    Index
+}
+
+
+impl Watch {
+    pub fn memory(addr: usize, kind: WatchKind) -> Self {
+        Watch::Memory { start: addr, end: addr, kind }
+    }
+
+    pub fn matches(&self, access: &Access) -> bool {
+        match (*self, access.location) {
+            (Watch::Memory { start, end, kind }, Location::Memory(addr)) => {
+                (start..=end).contains(&addr) && kind.matches(access.kind)
+            }
+            (Watch::Register { x }, Location::V(reg)) => x == reg && access.kind == AccessKind::Write,
+            (Watch::Index, Location::I) => access.kind == AccessKind::Write,
+            _ => false
+        }
+    }
+}
+
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watch::Memory { start, end, kind } => {
+                let kind: &str = match kind {
+                    WatchKind::Read => "read",
+                    WatchKind::Write => "write",
+                    WatchKind::ReadWrite => "access"
+                };
+
+                if start == end {
+                    write!(f, "{} 0x{:04X}", kind, start)
+                } else {
+                    write!(f, "{} 0x{:04X}-0x{:04X}", kind, start, end)
+                }
+            }
+            Watch::Register { x } => write!(f, "write V{:X}", x),
+            Watch::Index => write!(f, "write I")
+        }
+    }
+}
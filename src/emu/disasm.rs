@@ -0,0 +1,256 @@
+use std::{
+    collections::BTreeMap,
+    fmt
+};
+use super::cpu::{
+    ENTRY_POINT,
+    instruction::{
+        Op,
+        Syntax
+    }
+};
+
+const DATA_ROW: usize = 8;     // bytes per data line
+const ADDRESS_SPACE: usize = 0x10000;
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LabelKind {
+    Subroutine,     // target of 2NNN
+    Jump,           // target of 1NNN, BNNN or a branch
+    Data            // target of ANNN outside traced code
+}
+
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Entry {
+    Code { addr: u16, bytes: Vec<u8>, op: Op },
+    Data { addr: u16, bytes: Vec<u8> }
+}
+
+
+/// Disassembly of a rom where only code reachable from the entry point is
+/// decoded, everything else is shown as raw bytes.
+pub struct Listing {
+    entries: Vec<Entry>,
+    labels: BTreeMap<u16, LabelKind>,
+    syntax: Syntax
+}
+
+
+impl Listing {
+    /// Traces `rom` loaded at [`ENTRY_POINT`] following jumps, calls and skips.
+    /// Bytes past the end of the 16-bit address space are left out.
+    pub fn trace(rom: &[u8]) -> Self {
+        let origin: usize = ENTRY_POINT as usize;
+        let rom: &[u8] = &rom[..rom.len().min(ADDRESS_SPACE - origin)];
+        let end: usize = origin + rom.len();
+        let in_rom = |addr: usize| (origin..end).contains(&addr);
+
+        let mut code: BTreeMap<u16, Op> = BTreeMap::new();
+        let mut labels: BTreeMap<u16, LabelKind> = BTreeMap::new();
+        let mut pending: Vec<u16> = vec![ENTRY_POINT];
+
+        while let Some(addr) = pending.pop() {
+            if !in_rom(addr as usize) || !in_rom(addr as usize + 1) || code.contains_key(&addr) {
+                continue;
+            }
+
+            let op: Op = Self::decode(rom, addr);
+
+            if matches!(op, Op::Unknown { .. } | Op::Sys { .. }) {
+                continue;
+            }
+
+            code.insert(addr, op);
+
+            // None past the end of the address space
+            let next: Option<u16> = addr.checked_add(op.size());
+
+            match op {
+                Op::Jump { nnn } => {
+                    labels.insert(nnn, LabelKind::Jump);
+                    pending.push(nnn);
+                }
+                Op::JumpOffset { nnn, .. } => {
+                    // jump table, only its first entry is known to be code
+                    labels.insert(nnn, LabelKind::Jump);
+                    pending.push(nnn);
+                }
+                Op::Call { nnn } => {
+                    labels.insert(nnn, LabelKind::Subroutine);
+                    pending.push(nnn);
+                    pending.extend(next);
+                }
+                Op::Return | Op::Exit => (),
+                Op::SkipEqImm { .. } | Op::SkipNeImm { .. } | Op::SkipEqReg { .. } |
+                Op::SkipNeReg { .. } | Op::SkipKeyPressed { .. } | Op::SkipKeyReleased { .. } => {
+                    pending.extend(next);
+
+                    if let Some(next) = next.filter(|next| in_rom(*next as usize) && in_rom(*next as usize + 1)) {
+                        pending.extend(next.checked_add(Self::decode(rom, next).size()));
+                    }
+                }
+                Op::LoadIndex { nnn } => {
+                    labels.entry(nnn).or_insert(LabelKind::Data);
+                    pending.extend(next);
+                }
+                Op::LoadLongIndex { nnnn } => {
+                    labels.entry(nnnn).or_insert(LabelKind::Data);
+                    pending.extend(next);
+                }
+                _ => {
+                    pending.extend(next);
+                }
+            }
+        }
+
+        labels.retain(|addr, _| in_rom(*addr as usize));
+
+        Self {
+            entries: Self::build_entries(rom, &code, &labels),
+            labels,
+            syntax: Syntax::default()
+        }
+    }
+
+    pub fn with_syntax(mut self, syntax: Syntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn labels(&self) -> &BTreeMap<u16, LabelKind> {
+        &self.labels
+    }
+
+    pub fn label_name(&self, addr: u16) -> Option<String> {
+        self.labels.get(&addr).map(|kind| {
+            let prefix: &str = match kind {
+                LabelKind::Subroutine => "sub",
+                LabelKind::Jump => "loc",
+                LabelKind::Data => "data"
+            };
+
+            format!("{}_{:04X}", prefix, addr)
+        })
+    }
+
+    fn decode(rom: &[u8], addr: u16) -> Op {
+        let byte = |addr: usize| rom.get(addr - ENTRY_POINT as usize).copied().unwrap_or(0) as u16;
+        let addr: usize = addr as usize;
+
+        Op::decode(
+            (byte(addr) << 8) | byte(addr + 1),
+            (byte(addr + 2) << 8) | byte(addr + 3)
+        )
+    }
+
+    fn build_entries(rom: &[u8], code: &BTreeMap<u16, Op>, labels: &BTreeMap<u16, LabelKind>) -> Vec<Entry> {
+        let origin: usize = ENTRY_POINT as usize;
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut addr: usize = origin;
+
+        while addr < origin + rom.len() {
+            if let Some(op) = code.get(&(addr as u16)) {
+                let size: usize = (op.size() as usize).min(origin + rom.len() - addr);
+
+                entries.push(Entry::Code {
+                    addr: addr as u16,
+                    bytes: rom[addr - origin..addr - origin + size].to_vec(),
+                    op: *op
+                });
+
+                addr += size;
+                continue;
+            }
+
+            // data runs until the next instruction, label or a full row
+            let begin: usize = addr;
+
+            addr += 1;
+
+            while addr < origin + rom.len()
+                && addr - begin < DATA_ROW
+                && !code.contains_key(&(addr as u16))
+                && !labels.contains_key(&(addr as u16)) {
+                addr += 1;
+            }
+
+            entries.push(Entry::Data {
+                addr: begin as u16,
+                bytes: rom[begin - origin..addr - origin].to_vec()
+            });
+        }
+
+        entries
+    }
+
+    fn fmt_op(&self, f: &mut fmt::Formatter, op: &Op) -> fmt::Result {
+        let target: Option<(u16, &str, &str)> = match *op {
+            Op::Jump { nnn } => Some((nnn, "jump", "JP")),
+            Op::Call { nnn } => Some((nnn, ":call", "CALL")),
+            Op::LoadIndex { nnn } => Some((nnn, "i :=", "LD I,")),
+            Op::LoadLongIndex { nnnn } => Some((nnnn, "i := long", "LD I,")),
+            Op::JumpOffset { nnn, .. } => Some((nnn, "jump0", "JP V0,")),
+            _ => None
+        };
+
+        match target.and_then(|(addr, octo, classic)| Some((self.label_name(addr)?, octo, classic))) {
+            Some((label, octo, classic)) => match self.syntax {
+                Syntax::Octo => write!(f, "{} {}", octo, label),
+                Syntax::Classic => write!(f, "{} {}", classic, label)
+            },
+            None => write!(f, "{}", op.disassemble(self.syntax))
+        }
+    }
+}
+
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in self.entries.iter() {
+            let (addr, bytes) = match entry {
+                Entry::Code { addr, bytes, .. } => (*addr, bytes),
+                Entry::Data { addr, bytes } => (*addr, bytes)
+            };
+
+            if let Some(label) = self.label_name(addr) {
+                match self.syntax {
+                    Syntax::Octo => writeln!(f, ": {}", label)?,
+                    Syntax::Classic => writeln!(f, "{}:", label)?
+                }
+            }
+
+            let raw: String = bytes
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            write!(f, "    {:04X}  {:<24}", addr, raw)?;
+
+            match entry {
+                Entry::Code { op, .. } => self.fmt_op(f, op)?,
+                Entry::Data { bytes, .. } => {
+                    let values: Vec<String> = bytes
+                        .iter()
+                        .map(|byte| format!("0x{:02X}", byte))
+                        .collect();
+
+                    match self.syntax {
+                        Syntax::Octo => write!(f, "{}", values.join(" "))?,
+                        Syntax::Classic => write!(f, "DB {}", values.join(", "))?
+                    }
+                }
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,31 +1,217 @@
-pub const WIDTH: u8 = 64;
-pub const HEIGHT: u8 = 32;
+use super::error::Chip8Error;
+use super::savestate::{
+    Reader,
+    Writer
+};
+
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Number of XO-CHIP bitplanes.
+pub const PLANES: usize = 2;
+
+/// RGBA colours indexed by pixel value: background, plane 1, plane 2, both planes.
+pub type Palette = [[f32; 4]; 1 << PLANES];
+
+pub const DEFAULT_PALETTE: Palette = [
+    [0.0, 0.5, 0.0, 1.0],
+    [0.0, 0.0, 0.0, 1.0],
+    [0.0, 0.8, 0.0, 1.0],
+    [0.0, 0.25, 0.0, 1.0]
+];
+
+/// Parses 2 or 4 comma separated RGB hex colours such as `000000,ffffff`.
+/// With two colours both XO-CHIP planes use the second one.
+pub fn parse_palette(colours: &str) -> Result<Palette, String> {
+    let parse = |colour: &str| {
+        let hex: &str = colour.trim().trim_start_matches('#');
+        let rgb: u32 = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or(format!("bad colour '{}', expected rrggbb", colour))?;
+
+        Ok([
+            (rgb >> 16 & 0xFF) as f32 / 255.0,
+            (rgb >> 8 & 0xFF) as f32 / 255.0,
+            (rgb & 0xFF) as f32 / 255.0,
+            1.0
+        ])
+    };
+    let colours: Vec<[f32; 4]> = colours.split(',').map(parse).collect::<Result<_, String>>()?;
+
+    match colours.as_slice() {
+        [background, plane] => Ok([*background, *plane, *plane, *plane]),
+        [background, plane1, plane2, both] => Ok([*background, *plane1, *plane2, *both]),
+        _ => Err(String::from("a palette needs 2 or 4 colours"))
+    }
+}
+
 
 pub struct Display {
-    display: [bool; 2048]
+    display: Vec<u8>,   // one bit per plane for every pixel
+    hires: bool,        // SUPER-CHIP 128x64 mode
+    planes: u8          // XO-CHIP planes affected by drawing, clearing and scrolling
+}
+
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 
 impl Display {
     pub fn new() -> Self {
         Self {
-            display: [false; 2048]
+            display: vec![0; LORES_WIDTH * LORES_HEIGHT],
+            hires: false,
+            planes: 0b01
         }
     }
 
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switches between 64x32 and 128x64 resolution. The screen is cleared.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.display = vec![0; self.width() * self.height()];
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANES) - 1) as u8;
+    }
+
+    /// Clears the selected planes.
     pub fn clear(&mut self) {
-        self.display.fill_with(|| false);
+        let mask: u8 = !self.planes;
+
+        self.display.iter_mut().for_each(|pixel| *pixel &= mask);
     }
 
-    pub fn read_pixel(&mut self, addr: usize) -> bool {
+    /// Palette index of a pixel combining all planes.
+    pub fn read_pixel(&self, addr: usize) -> u8 {
         self.display[addr]
     }
 
-    pub fn write_pixel(&mut self, addr: usize, pixel: bool) {
-        self.display[addr] = pixel;
+    pub fn read_plane(&self, addr: usize, plane: u8) -> bool {
+        self.display[addr] & plane != 0
+    }
+
+    pub fn write_plane(&mut self, addr: usize, plane: u8, pixel: bool) {
+        if pixel {
+            self.display[addr] |= plane;
+        } else {
+            self.display[addr] &= !plane;
+        }
     }
 
     pub fn get_size(&self) -> usize {
         self.display.len()
     }
-}
\ No newline at end of file
+
+    pub fn save_state(&self, w: &mut Writer) {
+        w.bool(self.hires);
+        w.u8(self.planes);
+        w.bytes(&self.display);
+    }
+
+    pub fn from_state(r: &mut Reader) -> Result<Self, Chip8Error> {
+        let mut display: Display = Self::new();
+
+        display.set_hires(r.bool()?);
+        display.planes = r.u8()?;
+
+        if display.planes >> PLANES != 0 {
+            return Err(Chip8Error::BadSaveState { reason: "unknown bitplanes selected" });
+        }
+
+        let pixels: &[u8] = r.bytes()?;
+
+        if pixels.len() != display.display.len() {
+            return Err(Chip8Error::BadSaveState { reason: "framebuffer has the wrong size" });
+        }
+
+        if pixels.iter().any(|pixel| pixel >> PLANES != 0) {
+            return Err(Chip8Error::BadSaveState { reason: "pixel on an unknown bitplane" });
+        }
+
+        display.display.copy_from_slice(pixels);
+
+        Ok(display)
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.display
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        let shift: usize = (rows * self.width()).min(self.display.len());
+        let len: usize = self.display.len();
+
+        self.scroll(|display| {
+            display.rotate_left(shift);
+            display[len - shift..].fill(0);
+        });
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        let shift: usize = (rows * self.width()).min(self.display.len());
+
+        self.scroll(|display| {
+            display.rotate_right(shift);
+            display[..shift].fill(0);
+        });
+    }
+
+    pub fn scroll_right(&mut self, columns: usize) {
+        let width: usize = self.width();
+        let shift: usize = columns.min(width);
+
+        self.scroll(|display| {
+            for row in display.chunks_mut(width) {
+                row.rotate_right(shift);
+                row[..shift].fill(0);
+            }
+        });
+    }
+
+    pub fn scroll_left(&mut self, columns: usize) {
+        let width: usize = self.width();
+        let shift: usize = columns.min(width);
+
+        self.scroll(|display| {
+            for row in display.chunks_mut(width) {
+                row.rotate_left(shift);
+                row[width - shift..].fill(0);
+            }
+        });
+    }
+
+    // moves only the selected planes, the others stay in place
+    fn scroll(&mut self, mut shift: impl FnMut(&mut [u8])) {
+        let mut moved: Vec<u8> = self.display.iter().map(|pixel| pixel & self.planes).collect();
+
+        shift(&mut moved);
+
+        for (pixel, moved) in self.display.iter_mut().zip(moved) {
+            *pixel = (*pixel & !self.planes) | moved;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    fmt,
+    io
+};
+
+
+/// What happens on stack overflow/underflow and out of bounds memory access.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum FaultPolicy {
+    #[default]
+    Halt,   // stop the machine and report the error
+    Wrap,   // wrap addresses and stack pointer around like real hardware
+    Trap    // pause on the faulting instruction for the debugger
+}
+
+
+/// Everything that can go wrong while loading or running a rom.
+#[derive(Debug)]
+pub enum Chip8Error {
+    Io(io::Error),
+    RomTooLarge { size: usize, max_size: usize },
+    UnknownOpcode { addr: u16, opcode: u16 },
+    StackOverflow { addr: u16 },
+    StackUnderflow { addr: u16 },
+    OutOfBounds { addr: usize },
+    BadSaveState { reason: &'static str },
+    BadMovie { line: usize, reason: &'static str },
+    MovieMismatch { what: &'static str, expected: u64, actual: u64 },  // replay diverged from the recording
+    BadKeyMap { line: usize, reason: &'static str },
+    BadConfig { line: usize, reason: String }
+}
+
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::Io(err) => write!(f, "i/o error: {}", err),
+            Chip8Error::RomTooLarge { size, max_size } => {
+                write!(f, "rom is {} bytes but only {} fit into memory", size, max_size)
+            }
+            Chip8Error::UnknownOpcode { addr, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:04X}", opcode, addr)
+            }
+            Chip8Error::StackOverflow { addr } => write!(f, "stack overflow at {:04X}", addr),
+            Chip8Error::StackUnderflow { addr } => write!(f, "stack underflow at {:04X}", addr),
+            Chip8Error::OutOfBounds { addr } => write!(f, "memory access out of bounds at {:04X}", addr),
+            Chip8Error::BadSaveState { reason } => write!(f, "invalid save state: {}", reason),
+            Chip8Error::BadMovie { line, reason } => write!(f, "invalid movie, line {}: {}", line, reason),
+            Chip8Error::MovieMismatch { what, expected, actual } => {
+                write!(f, "{} hash is {:016x} but the movie expects {:016x}", what, actual, expected)
+            }
+            Chip8Error::BadKeyMap { line, reason } => write!(f, "invalid keymap, line {}: {}", line, reason),
+            Chip8Error::BadConfig { line, reason } => write!(f, "invalid config, line {}: {}", line, reason)
+        }
+    }
+}
+
+
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Chip8Error::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+
+impl From<io::Error> for Chip8Error {
+    fn from(err: io::Error) -> Self {
+        Chip8Error::Io(err)
+    }
+}
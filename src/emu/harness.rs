@@ -0,0 +1,288 @@
+//! Headless rom runner for tests.
+//!
+//! A [`Harness`] runs a rom for a number of frames with scripted key presses
+//! and compares the display against a golden image. Goldens ending in `.png`
+//! are images (with the `png` feature), anything else is ASCII art with one
+//! character per pixel: `.` background, `#` plane 1, `+` plane 2, `@` both.
+//!
+//! Missing goldens are an error. Running with `CHIP8_BLESS=1` writes the
+//! current display to the golden instead of comparing.
+
+use std::{
+    env,
+    fmt,
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf
+    }
+};
+use super::{
+    Machine,
+    display::Display,
+    error::Chip8Error,
+    platform::Platform
+};
+
+pub const BLESS_VAR: &str = "CHIP8_BLESS";
+
+const ASCII_PIXELS: [char; 4] = ['.', '#', '+', '@'];
+#[cfg(feature = "png")]
+const GRAY_PIXELS: [u8; 4] = [0x00, 0xFF, 0xAA, 0x55];
+
+
+struct KeyEvent {
+    frame: u64,
+    key: usize,
+    pressed: bool
+}
+
+
+pub struct Harness {
+    machine: Machine,
+    events: Vec<KeyEvent>
+}
+
+
+impl Harness {
+    /// Loads `rom` on a COSMAC VIP with a fixed random seed.
+    pub fn new(rom: &[u8]) -> Result<Self, Chip8Error> {
+        Self::with_platform(Platform::default(), rom)
+    }
+
+    pub fn with_platform(platform: Platform, rom: &[u8]) -> Result<Self, Chip8Error> {
+        let mut machine: Machine = Machine::with_platform(platform);
+
+        machine.set_seed(0);
+        machine.init_from_bytes(rom)?;
+
+        Ok(Self::from_machine(machine))
+    }
+
+    /// Wraps a machine that already has a rom loaded.
+    pub fn from_machine(machine: Machine) -> Self {
+        Self {
+            machine,
+            events: Vec::new()
+        }
+    }
+
+    /// Presses `key` before `frame` runs.
+    pub fn press(&mut self, frame: u64, key: usize) -> &mut Self {
+        self.events.push(KeyEvent { frame, key, pressed: true });
+        self
+    }
+
+    /// Releases `key` before `frame` runs.
+    pub fn release(&mut self, frame: u64, key: usize) -> &mut Self {
+        self.events.push(KeyEvent { frame, key, pressed: false });
+        self
+    }
+
+    /// Holds `key` from `frame` for `frames` frames.
+    pub fn tap(&mut self, frame: u64, key: usize, frames: u64) -> &mut Self {
+        self.press(frame, key).release(frame + frames, key)
+    }
+
+    /// Runs `frames` more frames, applying the key events scheduled for them,
+    /// the queued host keys and the events of the machine's input sources.
+    pub fn run(&mut self, frames: u64) -> Result<&mut Self, Chip8Error> {
+        for _ in 0..frames {
+            let frame: u64 = self.machine.frame();
+
+            for event in self.events.iter().filter(|event| event.frame == frame) {
+                self.machine.set_key(event.key, event.pressed);
+            }
+
+            self.machine.apply_input();
+
+            self.machine.step_frame()?;
+        }
+
+        Ok(self)
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn ascii(&self) -> String {
+        to_ascii(self.machine.display())
+    }
+
+    /// Compares the display with the golden at `path`, or writes it when blessing.
+    pub fn check_golden<P: AsRef<Path>>(&self, path: P) -> Result<(), GoldenError> {
+        check_golden(self.machine.display(), path.as_ref())
+    }
+}
+
+
+/// Renders the display with one character per pixel and one line per row.
+pub fn to_ascii(display: &Display) -> String {
+    let mut ascii: String = String::new();
+
+    for row in display.pixels().chunks(display.width()) {
+        ascii.extend(row.iter().map(|pixel| ASCII_PIXELS[*pixel as usize & 3]));
+        ascii.push('\n');
+    }
+
+    ascii
+}
+
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(PathBuf, io::Error),
+    Missing(PathBuf),
+    Image(PathBuf, String),                                     // golden image cannot be decoded
+    Mismatch { path: PathBuf, expected: String, actual: String }    // both as ASCII art
+}
+
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            GoldenError::Missing(path) => {
+                write!(f, "{} does not exist, run with {}=1 to create it", path.display(), BLESS_VAR)
+            }
+            GoldenError::Image(path, reason) => write!(f, "{}: {}", path.display(), reason),
+            GoldenError::Mismatch { path, expected, actual } => {
+                let differing: usize = expected
+                    .chars()
+                    .zip(actual.chars())
+                    .filter(|(expected, actual)| expected != actual)
+                    .count();
+
+                write!(
+                    f,
+                    "display differs from {} in {} pixels, run with {}=1 to accept it\nexpected:\n{}actual:\n{}",
+                    path.display(), differing, BLESS_VAR, expected, actual
+                )
+            }
+        }
+    }
+}
+
+
+impl std::error::Error for GoldenError {}
+
+
+/// True when goldens should be rewritten instead of compared.
+pub fn blessing() -> bool {
+    env::var(BLESS_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+
+pub fn check_golden(display: &Display, path: &Path) -> Result<(), GoldenError> {
+    if blessing() {
+        return write_display(display, path);
+    }
+
+    if !path.exists() {
+        return Err(GoldenError::Missing(path.to_path_buf()));
+    }
+
+    let actual: String = to_ascii(display);
+    let expected: String = if is_png(path) {
+        read_png(path)?
+    } else {
+        fs::read_to_string(path).map_err(|err| GoldenError::Io(path.to_path_buf(), err))?
+    };
+
+    if expected.trim_end() != actual.trim_end() {
+        return Err(GoldenError::Mismatch { path: path.to_path_buf(), expected, actual });
+    }
+
+    Ok(())
+}
+
+
+/// Writes the display to `path` as a PNG image or ASCII art, creating missing directories.
+pub fn write_display(display: &Display, path: &Path) -> Result<(), GoldenError> {
+    let io_error = |err: io::Error| GoldenError::Io(path.to_path_buf(), err);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    if is_png(path) {
+        return write_png(display, path);
+    }
+
+    fs::write(path, to_ascii(display)).map_err(io_error)
+}
+
+
+fn is_png(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+
+#[cfg(feature = "png")]
+fn write_png(display: &Display, path: &Path) -> Result<(), GoldenError> {
+    let file: fs::File = fs::File::create(path).map_err(|err| GoldenError::Io(path.to_path_buf(), err))?;
+    let mut encoder: png::Encoder<io::BufWriter<fs::File>> = png::Encoder::new(
+        io::BufWriter::new(file),
+        display.width() as u32,
+        display.height() as u32
+    );
+
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let gray: Vec<u8> = display.pixels().iter().map(|pixel| GRAY_PIXELS[*pixel as usize & 3]).collect();
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&gray))
+        .map_err(|err| GoldenError::Image(path.to_path_buf(), err.to_string()))
+}
+
+
+#[cfg(feature = "png")]
+fn read_png(path: &Path) -> Result<String, GoldenError> {
+    let image_error = |reason: String| GoldenError::Image(path.to_path_buf(), reason);
+    let file: fs::File = fs::File::open(path).map_err(|err| GoldenError::Io(path.to_path_buf(), err))?;
+    let mut reader = png::Decoder::new(file).read_info().map_err(|err| image_error(err.to_string()))?;
+    let mut buffer: Vec<u8> = vec![0; reader.output_buffer_size()];
+    let info: png::OutputInfo = reader.next_frame(&mut buffer).map_err(|err| image_error(err.to_string()))?;
+
+    if info.color_type != png::ColorType::Grayscale || info.bit_depth != png::BitDepth::Eight {
+        return Err(image_error(String::from("golden images must be 8-bit grayscale")));
+    }
+
+    let mut ascii: String = String::new();
+
+    for row in buffer[..info.buffer_size()].chunks(info.width as usize) {
+        for gray in row {
+            let pixel: usize = GRAY_PIXELS
+                .iter()
+                .position(|level| level == gray)
+                .ok_or_else(|| image_error(format!("unexpected gray level {}", gray)))?;
+
+            ascii.push(ASCII_PIXELS[pixel]);
+        }
+
+        ascii.push('\n');
+    }
+
+    Ok(ascii)
+}
+
+
+#[cfg(not(feature = "png"))]
+fn write_png(_display: &Display, path: &Path) -> Result<(), GoldenError> {
+    Err(GoldenError::Image(path.to_path_buf(), String::from("built without the png feature")))
+}
+
+
+#[cfg(not(feature = "png"))]
+fn read_png(path: &Path) -> Result<String, GoldenError> {
+    Err(GoldenError::Image(path.to_path_buf(), String::from("built without the png feature")))
+}
@@ -0,0 +1,158 @@
+//! Game controllers on the hex keypad.
+//!
+//! An [`InputSource`] reports controller buttons going down and up. The
+//! machine polls its sources every frame and presses the hex keys bound to
+//! the button names in its gamepad [`KeyMap`](super::keymap::KeyMap), by
+//! default the D-pad on 2/4/6/8 and the south face button on 5. Controllers
+//! plugged into the host are read by [`Gamepads`] with the `gamepad` feature.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    rc::Rc
+};
+
+
+/// Buttons in the layout of common gamepads, named by position.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum PadButton {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    South,          // A on Xbox, cross on PlayStation
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    LeftThumb,      // pressing the left stick
+    RightThumb,
+    Select,
+    Start,
+    Mode,           // the logo button in the middle
+    Other(u32)      // any other button by the code its driver reports
+}
+
+
+/// The name used in bindings, e.g. `DPadUp` or `Button704` for [`PadButton::Other`].
+impl fmt::Display for PadButton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PadButton::Other(index) => write!(f, "Button{}", index),
+            button => write!(f, "{:?}", button)
+        }
+    }
+}
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PadEvent {
+    pub button: PadButton,
+    pub pressed: bool
+}
+
+
+pub trait InputSource {
+    /// Oldest event that has not been returned yet, None when there are no more.
+    fn poll(&mut self) -> Option<PadEvent>;
+}
+
+
+/// Controller driven by code, e.g. by tests. Clones share their event queue.
+#[derive(Clone, Default)]
+pub struct VirtualController {
+    events: Rc<RefCell<VecDeque<PadEvent>>>
+}
+
+
+impl VirtualController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&self, button: PadButton) {
+        self.events.borrow_mut().push_back(PadEvent { button, pressed: true });
+    }
+
+    pub fn release(&self, button: PadButton) {
+        self.events.borrow_mut().push_back(PadEvent { button, pressed: false });
+    }
+}
+
+
+impl InputSource for VirtualController {
+    fn poll(&mut self) -> Option<PadEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+}
+
+
+/// Every controller plugged into the host, read through gilrs. Buttons are
+/// mapped by the name gilrs gives them, buttons without one become
+/// [`PadButton::Other`] with their code.
+#[cfg(feature = "gamepad")]
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs
+}
+
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            gilrs: gilrs::Gilrs::new()?
+        })
+    }
+}
+
+
+#[cfg(feature = "gamepad")]
+impl InputSource for Gamepads {
+    fn poll(&mut self) -> Option<PadEvent> {
+        while let Some(event) = self.gilrs.next_event() {
+            let (button, code, pressed) = match event.event {
+                gilrs::EventType::ButtonPressed(button, code) => (button, code, true),
+                gilrs::EventType::ButtonReleased(button, code) => (button, code, false),
+                _ => continue
+            };
+
+            let button: PadButton = pad_button(button).unwrap_or(PadButton::Other(code.into_u32()));
+
+            return Some(PadEvent { button, pressed });
+        }
+
+        None
+    }
+}
+
+
+#[cfg(feature = "gamepad")]
+fn pad_button(button: gilrs::Button) -> Option<PadButton> {
+    use gilrs::Button;
+
+    match button {
+        Button::DPadUp => Some(PadButton::DPadUp),
+        Button::DPadDown => Some(PadButton::DPadDown),
+        Button::DPadLeft => Some(PadButton::DPadLeft),
+        Button::DPadRight => Some(PadButton::DPadRight),
+        Button::South => Some(PadButton::South),
+        Button::East => Some(PadButton::East),
+        Button::West => Some(PadButton::West),
+        Button::North => Some(PadButton::North),
+        // gilrs calls the shoulder buttons triggers and the triggers Trigger2
+        Button::LeftTrigger => Some(PadButton::LeftShoulder),
+        Button::RightTrigger => Some(PadButton::RightShoulder),
+        Button::LeftTrigger2 => Some(PadButton::LeftTrigger),
+        Button::RightTrigger2 => Some(PadButton::RightTrigger),
+        Button::LeftThumb => Some(PadButton::LeftThumb),
+        Button::RightThumb => Some(PadButton::RightThumb),
+        Button::Select => Some(PadButton::Select),
+        Button::Start => Some(PadButton::Start),
+        Button::Mode => Some(PadButton::Mode),
+        _ => None       // Unknown and the rare C and Z buttons
+    }
+}
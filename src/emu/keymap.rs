@@ -0,0 +1,280 @@
+//! Host keyboard bindings for the hex keypad.
+//!
+//! Host keys are named after the piston `Key` variants, e.g. `D1`, `Q`, `Up`
+//! or `NumPad5`, in any case. A host key presses one hex key, a hex key can
+//! have any number of host keys and stays down while any of them is held,
+//! see [`HeldKeys`]. Bound host keys take precedence over the window
+//! hotkeys such as F5 or Backspace, only Escape always quits. A keymap file
+//! binds one hex key per line, `#` starts a comment:
+//!
+//! ```text
+//! 5 W Up
+//! 8 S Down
+//! ```
+
+use std::{
+    collections::{
+        HashMap,
+        hash_map
+    },
+    fmt,
+    fs,
+    str::FromStr
+};
+use super::error::Chip8Error;
+
+// the VIP keypad order, row by row
+const KEYPAD: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF
+];
+
+const QWERTY: [&str; 16] = [
+    "D1", "D2", "D3", "D4",
+    "Q", "W", "E", "R",
+    "A", "S", "D", "F",
+    "Z", "X", "C", "V"
+];
+
+// the unshifted top row gives &, é, " and ', piston has no key for é
+const AZERTY: [&str; 16] = [
+    "Ampersand", "D2", "Quotedbl", "Quote",
+    "A", "Z", "E", "R",
+    "Q", "S", "D", "F",
+    "W", "X", "C", "V"
+];
+
+const DVORAK: [&str; 16] = [
+    "D1", "D2", "D3", "D4",
+    "Quote", "Comma", "Period", "P",
+    "A", "O", "E", "U",
+    "Semicolon", "Q", "J", "K"
+];
+
+// digits on their own keys so 2/4/6/8 games get the numpad arrows, A-F around them
+const NUMPAD: [&str; 16] = [
+    "NumPad1", "NumPad2", "NumPad3", "NumPadMinus",
+    "NumPad4", "NumPad5", "NumPad6", "NumPadPlus",
+    "NumPad7", "NumPad8", "NumPad9", "NumPadEnter",
+    "NumPadDivide", "NumPad0", "NumPadMultiply", "NumPadPeriod"
+];
+
+// button names of input::PadButton
+const GAMEPAD: [(&str, u8); 12] = [
+    ("DPadUp", 0x2), ("DPadLeft", 0x4), ("DPadRight", 0x6), ("DPadDown", 0x8),
+    ("South", 0x5), ("East", 0x0), ("West", 0xA), ("North", 0xB),
+    ("LeftShoulder", 0x1), ("RightShoulder", 0x3), ("Select", 0xE), ("Start", 0xF)
+];
+
+
+/// Preset bindings, all but the numpad put the keypad on the same physical keys.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Dvorak,
+    Numpad
+}
+
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "dvorak" => Ok(Layout::Dvorak),
+            "numpad" => Ok(Layout::Numpad),
+            _ => Err(format!("unknown layout '{}', expected qwerty, azerty, dvorak or numpad", name))
+        }
+    }
+}
+
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layout::Qwerty => write!(f, "qwerty"),
+            Layout::Azerty => write!(f, "azerty"),
+            Layout::Dvorak => write!(f, "dvorak"),
+            Layout::Numpad => write!(f, "numpad")
+        }
+    }
+}
+
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct KeyMap {
+    bindings: HashMap<String, u8>   // lowercase host key name to hex key
+}
+
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl KeyMap {
+    /// QWERTY bindings.
+    pub fn new() -> Self {
+        Self::with_layout(Layout::default())
+    }
+
+    pub fn with_layout(layout: Layout) -> Self {
+        let hosts: [&str; 16] = match layout {
+            Layout::Qwerty => QWERTY,
+            Layout::Azerty => AZERTY,
+            Layout::Dvorak => DVORAK,
+            Layout::Numpad => NUMPAD
+        };
+        let mut keymap: KeyMap = Self::empty();
+
+        for (host, key) in hosts.iter().zip(KEYPAD) {
+            keymap.bind(host, key);
+        }
+
+        // AZERTY digits need shift, which piston reports as the digit keys
+        if layout == Layout::Azerty {
+            for (host, key) in QWERTY[..4].iter().zip(KEYPAD) {
+                keymap.bind(host, key);
+            }
+        }
+
+        keymap
+    }
+
+    /// Controller bindings with the D-pad on 2/4/6/8 and the face buttons on 5, 0, A and B.
+    pub fn gamepad() -> Self {
+        let mut keymap: KeyMap = Self::empty();
+
+        for (button, key) in GAMEPAD {
+            keymap.bind(button, key);
+        }
+
+        keymap
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new()
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, Chip8Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Makes `host` press `key`, replacing what `host` was bound to before.
+    pub fn bind(&mut self, host: &str, key: u8) {
+        self.bindings.insert(host.to_lowercase(), key & 0xF);
+    }
+
+    pub fn unbind(&mut self, host: &str) {
+        self.bindings.remove(&host.to_lowercase());
+    }
+
+    /// Hex key pressed by the host key `host`.
+    pub fn key(&self, host: &str) -> Option<u8> {
+        self.bindings.get(&host.to_lowercase()).copied()
+    }
+
+    /// Host keys bound to `key`, sorted by name.
+    pub fn hosts(&self, key: u8) -> Vec<&str> {
+        let mut hosts: Vec<&str> = self.bindings
+            .iter()
+            .filter(|(_, bound)| **bound == key)
+            .map(|(host, _)| host.as_str())
+            .collect();
+
+        hosts.sort();
+        hosts
+    }
+}
+
+
+impl FromStr for KeyMap {
+    type Err = Chip8Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut keymap: KeyMap = KeyMap::empty();
+
+        for (index, line) in text.lines().enumerate() {
+            let bad = |reason: &'static str| Chip8Error::BadKeyMap { line: index + 1, reason };
+            let code: &str = line.split('#').next().unwrap_or_default();
+            let mut fields = code.split_whitespace();
+
+            let Some(key) = fields.next() else {
+                continue;
+            };
+            let key: u8 = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|key| *key < 16)
+                .ok_or(bad("keypad key must be a hex digit"))?;
+            let hosts: Vec<&str> = fields.collect();
+
+            if hosts.is_empty() {
+                return Err(bad("no host key given"));
+            }
+
+            for host in hosts {
+                keymap.bind(host, key);
+            }
+        }
+
+        Ok(keymap)
+    }
+}
+
+
+/// Host keys held down and the hex keys they press.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct HeldKeys {
+    hosts: HashMap<String, u8>,     // lowercase host key name to the hex key it pressed
+    counts: [usize; 16]             // held host keys per hex key
+}
+
+
+impl HeldKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Holds `host` down, returns the hex key it presses through `keymap`.
+    /// Repeated presses of a held host key are counted once.
+    pub fn press(&mut self, keymap: &KeyMap, host: &str) -> Option<u8> {
+        let key: u8 = keymap.key(host)?;
+        let host: String = host.to_lowercase();
+
+        if let hash_map::Entry::Vacant(entry) = self.hosts.entry(host) {
+            entry.insert(key);
+            self.counts[key as usize] += 1;
+        }
+
+        Some(key)
+    }
+
+    /// Lets go of `host`, returns the hex key it pressed when no other host key holds it.
+    /// The key pressed at the time counts, even if `host` was rebound since.
+    pub fn release(&mut self, host: &str) -> Option<u8> {
+        let key: u8 = self.hosts.remove(&host.to_lowercase())?;
+
+        self.counts[key as usize] -= 1;
+
+        (self.counts[key as usize] == 0).then_some(key)
+    }
+
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.counts[key as usize & 0xF] > 0
+    }
+
+    pub fn clear(&mut self) {
+        self.hosts.clear();
+        self.counts = [0; 16];
+    }
+}
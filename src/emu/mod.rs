@@ -1,67 +1,543 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf
+};
+#[cfg(feature = "gui")]
 use std::time;
+#[cfg(feature = "gui")]
 use piston_window::{
     Button,
     Key
 };
 
-mod window;
-mod cpu;
-mod ram;
-mod display;
+#[cfg(feature = "gui")]
+pub mod window;
+pub mod asm;
+pub mod audio;
+pub mod bus;
+pub mod config;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod ram;
+pub mod display;
+pub mod error;
+pub mod harness;
+pub mod input;
+pub mod keymap;
+pub mod movie;
+pub mod platform;
+pub mod rewind;
+pub mod savestate;
+pub mod trace;
+
+use error::{
+    Chip8Error,
+    FaultPolicy
+};
 
 
-#[allow(dead_code)]
-#[derive(PartialEq)]
-enum State {
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum State {
     Quit,
     Running,
-    Paused
+    Paused,
+    Halted      // stopped by an error, cannot be resumed
 }
 
 
-#[allow(dead_code)]
 pub struct Machine {
     state: State,
     ram: ram::RAM,
-    window: window::Window,
-    display: display::Display,  // emulate original CHIP-8 resolution pixels
+    display: display::Display,  // lores or SUPER-CHIP hires framebuffer
+    audio: audio::Pattern,      // XO-CHIP sound pattern played while the sound timer runs
+    audio_sink: Box<dyn audio::AudioSink>,
     cpu: cpu::CPU,
-    keypad: [bool; 16],         // hexadecimal keypad 0x0 - 0xF
+    keypad: [bool; 16],         // hexadecimal keypad 0x0 - 0xF, down while any source holds a key
+    keys: [bool; 16],           // keys set with set_key, by movies and save states
     rom_name: String,           // currently running rom
+    rom_hash: u64,              // identifies the rom in movies
+    palette: display::Palette,  // colours used by the window frontend
+    window_size: (u32, u32),    // initial window size in host pixels
+    keymap: keymap::KeyMap,     // host keys of the window frontend
+    gamepad_map: keymap::KeyMap,    // controller buttons of all input sources
+    held_keys: keymap::HeldKeys,    // host keys held down
+    key_queue: VecDeque<(String, bool)>,    // host key presses and releases not applied yet
+    tapped_keys: [bool; 16],        // pressed since the frame began, down until it ends
+    input_sources: Vec<(Box<dyn input::InputSource>, keymap::HeldKeys)>,   // with the buttons each one holds
+    frame_limit: Option<u64>,   // the window closes after this many frames
+    frame: u64,                 // frames completed since the rom was loaded
+    rewind: Option<rewind::Rewind>, // history recorded at the end of every frame
+    recording: Option<movie::Movie>,
+    debugger: debugger::Debugger
+}
+
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 
-#[allow(dead_code)]
 impl Machine {
     pub fn new() -> Self {
+        Self::with_quirks(cpu::quirks::Quirks::default())
+    }
+
+    /// Machine with the memory size and quirks of the given platform.
+    pub fn with_platform(platform: platform::Platform) -> Self {
+        Self::with_platform_quirks(platform, platform.quirks())
+    }
+
+    /// Machine with the memory size of `platform` and custom quirks.
+    pub fn with_platform_quirks(platform: platform::Platform, quirks: cpu::quirks::Quirks) -> Self {
+        let mut machine: Machine = Self::with_quirks(quirks);
+
+        machine.ram = ram::RAM::with_size(platform.memory_size());
+        machine
+    }
+
+    pub fn with_quirks(quirks: cpu::quirks::Quirks) -> Self {
         Self {
             state: State::Paused,
             ram: ram::RAM::new(),
-            window: window::Window::new(String::from("CHIP-8 EMU"), 1200, 600),
             display: display::Display::new(),
-            cpu: cpu::CPU::new(),
+            audio: audio::Pattern::new(),
+            audio_sink: Box::new(audio::NullSink),
+            cpu: cpu::CPU::new(quirks),
             keypad: [false; 16],
+            keys: [false; 16],
             rom_name: String::new(),
+            rom_hash: movie::hash(&[]),
+            palette: display::DEFAULT_PALETTE,
+            window_size: (1200, 600),
+            keymap: keymap::KeyMap::new(),
+            gamepad_map: keymap::KeyMap::gamepad(),
+            held_keys: keymap::HeldKeys::new(),
+            key_queue: VecDeque::new(),
+            tapped_keys: [false; 16],
+            input_sources: Vec::new(),
+            frame_limit: None,
+            frame: 0,
+            rewind: None,
+            recording: None,
+            debugger: debugger::Debugger::new()
         }
     }
 
-    pub fn init(&mut self, rom_name: String) -> &mut Self {
-        self.cpu.bind_ram(&mut self.ram);
-        self.cpu.bind_display(&mut self.display);
-        self.cpu.bind_keypad(&mut self.keypad);
-        self.ram.load_rom(rom_name.clone());
+    pub fn init(&mut self, rom_name: String) -> Result<&mut Self, Chip8Error> {
+        let rom: Vec<u8> = fs::read(&rom_name)?;
+
+        self.init_from_bytes(&rom)?;
+        self.rom_name = rom_name;
+
+        Ok(self)
+    }
+
+    /// Loads a rom image that is already in memory, e.g. one embedded in a test.
+    pub fn init_from_bytes(&mut self, rom: &[u8]) -> Result<&mut Self, Chip8Error> {
+        self.ram.load_rom_bytes(rom)?;
+        self.rom_hash = movie::hash(rom);
         self.state = State::Running;
 
-        self
+        Ok(self)
+    }
+
+    /// Runs one 60hz frame worth of instructions and ticks the timers once.
+    ///
+    /// On error the machine is halted, or paused on the faulting instruction
+    /// when the fault policy is [`FaultPolicy::Trap`].
+    pub fn step_frame(&mut self) -> Result<(), Chip8Error> {
+        while !self.step_instruction()? {}
+
+        Ok(())
+    }
+
+    /// Executes a single instruction, returns true when it completed a frame.
+    pub fn step_instruction(&mut self) -> Result<bool, Chip8Error> {
+        let result: Result<bool, Chip8Error> = self.cpu.step(&mut bus::Bus::new(
+            &mut self.ram,
+            &mut self.display,
+            &self.keypad,
+            &mut self.audio,
+            self.frame
+        ));
+
+        match result {
+            Ok(true) => self.end_frame(),
+            Ok(false) => (),
+            Err(_) => {
+                self.state = match self.cpu.fault_policy() {
+                    FaultPolicy::Trap => State::Paused,
+                    _ => State::Halted
+                };
+            }
+        }
+
+        result
+    }
+
+    fn end_frame(&mut self) {
+        self.frame += 1;
+
+        if self.audio.is_custom() {
+            self.audio_sink.pattern(&self.audio);
+        }
+
+        self.audio_sink.buzzer(self.cpu.sound_timer() > 0);
+
+        if self.cpu.is_exited() {
+            self.state = State::Quit;
+        }
+
+        if let Some(movie) = self.recording.as_mut() {
+            let keys: u16 = (0..16).filter(|key| self.keypad[*key]).fold(0, |keys, key| keys | 1 << key);
+            movie.frames.push(keys);
+        }
+
+        self.tapped_keys = [false; 16];
+        self.update_keypad();
+
+        if self.rewind.is_some() {
+            let state: Vec<u8> = self.save_state();
+
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.push(state);
+            }
+        }
+    }
+
+    /// Starts recording the keypad of every frame. Call it before the first
+    /// frame, a movie always replays from power-on.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(movie::Movie::new(self));
+    }
+
+    /// Finishes the movie with hashes of the current framebuffer and memory.
+    pub fn stop_recording(&mut self) -> Option<movie::Movie> {
+        let mut movie: movie::Movie = self.recording.take()?;
+
+        movie.finish(self);
+        Some(movie)
     }
 
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Restarts the CXNN random sequence, movies record the seed used at power-on.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.cpu.set_seed(seed);
+    }
+
+    /// Switches the CXNN generator to `algorithm`, restarted from the current seed.
+    pub fn set_rng_algorithm(&mut self, algorithm: cpu::rng::Algorithm) {
+        self.cpu.set_rng(cpu::rng::Rng::new(algorithm, self.cpu.seed()));
+    }
+
+    /// Injects a generator, e.g. [`cpu::rng::Rng::scripted`] in tests.
+    pub fn set_rng(&mut self, rng: cpu::rng::Rng) {
+        self.cpu.set_rng(rng);
+    }
+
+    /// Starts recording every frame into `rewind`, None stops recording.
+    pub fn set_rewind(&mut self, rewind: Option<rewind::Rewind>) {
+        self.rewind = rewind;
+    }
+
+    pub fn rewind(&self) -> Option<&rewind::Rewind> {
+        self.rewind.as_ref()
+    }
+
+    /// Goes back one recorded frame, keeping the keys that are set right now
+    /// and dropping the frame from a movie being recorded. Returns false when
+    /// there is no more history.
+    pub fn rewind_frame(&mut self) -> Result<bool, Chip8Error> {
+        let Some(state) = self.rewind.as_mut().and_then(|rewind| rewind.pop()) else {
+            return Ok(false);
+        };
+
+        let state: Vec<u8> = state.to_vec();
+        let keys: [bool; 16] = self.keys;
+
+        self.restore_state(&state)?;
+        self.keys = keys;
+        self.update_keypad();
+        self.audio_sink.buzzer(false);
+
+        Ok(true)
+    }
+
+    /// Serializes the whole machine state, see [`savestate`] for the format.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w: savestate::Writer = savestate::Writer::new();
+
+        w.chunk(b"MACH", |w| w.u64(self.frame));
+        w.chunk(b"QRKS", |w| self.cpu.quirks().save_state(w));
+        w.chunk(b"CPU ", |w| self.cpu.save_state(w));
+        w.chunk(b"RAM ", |w| self.ram.save_state(w));
+        w.chunk(b"DISP", |w| self.display.save_state(w));
+        w.chunk(b"AUDI", |w| self.audio.save_state(w));
+        w.chunk(b"KEYS", |w| {
+            for pressed in self.keypad {
+                w.bool(pressed);
+            }
+        });
+
+        w.finish()
+    }
+
+    /// Restores a state written by [`Machine::save_state`], the machine is
+    /// left untouched when it is invalid. A movie being recorded is cut back
+    /// to the restored frame, states past its end are refused. Rewinding
+    /// goes back to the restored frame before the frames it replaced.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+        self.restore_state(state)?;
+
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.push(state.to_vec());
+        }
+
+        Ok(())
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+        let chunks = savestate::chunks(state)?;
+
+        let frame: u64 = savestate::chunk(&chunks, b"MACH")?.u64()?;
+
+        if self.recording.as_ref().is_some_and(|movie| frame > movie.frames.len() as u64) {
+            return Err(Chip8Error::BadSaveState { reason: "state is past the end of the movie being recorded" });
+        }
+
+        let quirks: cpu::quirks::Quirks = cpu::quirks::Quirks::from_state(&mut savestate::chunk(&chunks, b"QRKS")?)?;
+        let ram: ram::RAM = ram::RAM::from_state(&mut savestate::chunk(&chunks, b"RAM ")?)?;
+        let display: display::Display = display::Display::from_state(&mut savestate::chunk(&chunks, b"DISP")?)?;
+        let audio: audio::Pattern = audio::Pattern::from_state(&mut savestate::chunk(&chunks, b"AUDI")?)?;
+        let mut keypad: [bool; 16] = [false; 16];
+        let mut keys: savestate::Reader = savestate::chunk(&chunks, b"KEYS")?;
+
+        for pressed in keypad.iter_mut() {
+            *pressed = keys.bool()?;
+        }
+
+        // last, the cpu is only changed when the whole state is valid
+        self.cpu.load_state(&mut savestate::chunk(&chunks, b"CPU ")?, quirks)?;
+
+        if let Some(movie) = self.recording.as_mut() {
+            movie.frames.truncate(frame as usize);
+        }
+
+        self.frame = frame;
+        self.ram = ram;
+        self.display = display;
+        self.audio = audio;
+        self.keys = keypad;
+        self.tapped_keys = [false; 16];
+        self.update_keypad();
+        self.state = match (self.cpu.is_exited(), self.state) {
+            (true, _) => State::Quit,
+            (false, State::Paused) => State::Paused,
+            _ => State::Running
+        };
+
+        Ok(())
+    }
+
+    /// Saves the state into a slot file next to the rom.
+    pub fn save_slot(&self, slot: u8) -> Result<PathBuf, Chip8Error> {
+        let path: PathBuf = savestate::slot_path(&self.rom_name, slot);
+
+        fs::write(&path, self.save_state())?;
+
+        Ok(path)
+    }
+
+    pub fn load_slot(&mut self, slot: u8) -> Result<PathBuf, Chip8Error> {
+        let path: PathBuf = savestate::slot_path(&self.rom_name, slot);
+
+        self.load_state(&fs::read(&path)?)?;
+
+        Ok(path)
+    }
+
+    /// Renders the sound of the current frame, silence when the sound timer is zero.
+    pub fn render_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        if self.cpu.sound_timer() > 0 {
+            self.audio.render(out, sample_rate);
+        } else {
+            out.fill(0.0);
+        }
+    }
+
+    /// Replaces the sink notified about the buzzer every frame, silent by default.
+    pub fn set_audio_sink(&mut self, sink: Box<dyn audio::AudioSink>) {
+        self.audio_sink = sink;
+    }
+
+    /// Traces every executed instruction, None turns tracing off.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn trace::TraceSink>>) {
+        self.cpu.set_tracer(tracer);
+    }
+
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.cpu.set_fault_policy(policy);
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions: u32) {
+        self.cpu.set_instructions_per_frame(instructions);
+    }
+
+    pub fn set_palette(&mut self, palette: display::Palette) {
+        self.palette = palette;
+    }
+
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+    }
+
+    pub fn set_keymap(&mut self, keymap: keymap::KeyMap) {
+        self.keymap = keymap;
+    }
+
+    pub fn set_gamepad_map(&mut self, gamepad_map: keymap::KeyMap) {
+        self.gamepad_map = gamepad_map;
+    }
+
+    /// Adds a controller that is polled every frame besides the keyboard.
+    pub fn add_input_source(&mut self, source: Box<dyn input::InputSource>) {
+        self.input_sources.push((source, keymap::HeldKeys::new()));
+    }
+
+    /// Queues a press or release of the host key `host`, e.g. `Up` or `NumPad5`.
+    pub fn queue_key(&mut self, host: &str, pressed: bool) {
+        self.key_queue.push_back((host.to_string(), pressed));
+    }
+
+    /// Applies the queued host keys and then the events of the input sources
+    /// to the keypad, each in the order they happened. Every source holds
+    /// its own keys, a hex key is down while any of them holds it. A key
+    /// pressed and released before the same frame stays down for that frame,
+    /// so FX0A and EX9E still see a quick tap.
+    pub fn apply_input(&mut self) {
+        while let Some((host, pressed)) = self.key_queue.pop_front() {
+            if !pressed {
+                self.held_keys.release(&host);
+            } else if let Some(key) = self.held_keys.press(&self.keymap, &host) {
+                self.tapped_keys[key as usize] = true;
+            }
+        }
+
+        for (source, held) in self.input_sources.iter_mut() {
+            while let Some(event) = source.poll() {
+                let button: String = event.button.to_string();
+
+                if !event.pressed {
+                    held.release(&button);
+                } else if let Some(key) = held.press(&self.gamepad_map, &button) {
+                    self.tapped_keys[key as usize] = true;
+                }
+            }
+        }
+
+        self.update_keypad();
+    }
+
+    fn update_keypad(&mut self) {
+        for (key, pressed) in self.keypad.iter_mut().enumerate() {
+            let key: u8 = key as u8;
+
+            *pressed = self.keys[key as usize]
+                || self.tapped_keys[key as usize]
+                || self.held_keys.is_pressed(key)
+                || self.input_sources.iter().any(|(_, held)| held.is_pressed(key));
+        }
+    }
+
+    /// Closes the window after `frames` frames, None runs until the window is closed.
+    pub fn set_frame_limit(&mut self, frames: Option<u64>) {
+        self.frame_limit = frames;
+    }
+
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        self.keys[key & 0xF] = pressed;
+        self.update_keypad();
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Number of frames completed since the rom was loaded.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn debugger(&self) -> &debugger::Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut debugger::Debugger {
+        &mut self.debugger
+    }
+
+    pub fn cpu(&self) -> &cpu::CPU {
+        &self.cpu
+    }
+
+    pub fn ram(&self) -> &ram::RAM {
+        &self.ram
+    }
+
+    pub fn display(&self) -> &display::Display {
+        &self.display
+    }
+
+    pub fn audio(&self) -> &audio::Pattern {
+        &self.audio
+    }
+
+    pub fn keypad(&self) -> &[bool; 16] {
+        &self.keypad
+    }
+
+    pub fn rom_name(&self) -> &str {
+        &self.rom_name
+    }
+
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+}
+
+
+#[cfg(feature = "gui")]
+impl Machine {
     pub fn run(&mut self) {
-        while self.state != State::Quit {
+        let (width, height) = self.window_size;
+        let mut window: window::Window = window::Window::new(String::from("CHIP-8 EMU"), width, height);
+        window.set_palette(self.palette);
+
+        let mut slot: u8 = 0;           // save state slot used by the hotkeys
+        let mut rewinding: bool = false;  // backspace is held
+
+        while self.state != State::Quit && self.frame_limit.is_none_or(|limit| self.frame < limit) {
             let begin_time: time::Instant = time::Instant::now();
 
-            self.handle_input();
-            self.cpu.make_cycle();
-            self.window.update_screen(&mut self.display);
+            self.handle_input(&mut window, &mut slot, &mut rewinding);
+
+            if rewinding {
+                if let Err(err) = self.rewind_frame() {
+                    eprintln!("failed to rewind: {}", err);
+                }
+            } else if self.state == State::Running {
+                let stop: Result<debugger::Stop, Chip8Error> = self.run_frame();
+                self.report_stop(stop);
+            }
+
+            window.update_screen(&self.display);
 
             let mut end_time: time::Instant = time::Instant::now();
 
@@ -71,52 +547,84 @@ impl Machine {
         }
     }
 
-    fn handle_input(&mut self) {
-        match self.window.get_presssed_key() {
+    // breakpoints and watchpoints pause the window, stepping prints the registers to stderr
+    fn report_stop(&mut self, stop: Result<debugger::Stop, Chip8Error>) {
+        match stop {
+            Ok(debugger::Stop::Frame { .. }) | Ok(debugger::Stop::Exited) => return,
+            Ok(stop @ debugger::Stop::Breakpoint { .. }) | Ok(stop @ debugger::Stop::Watchpoint { .. }) => {
+                self.state = State::Paused;
+                eprintln!("{}", stop);
+            }
+            Ok(debugger::Stop::Step) => (),
+            Err(err) => eprintln!("emulation stopped: {}", err)
+        }
+
+        eprintln!("{}", self.registers());
+    }
+
+    // applies every press and release since the last frame in the order they happened
+    fn handle_input(&mut self, window: &mut window::Window, slot: &mut u8, rewinding: &mut bool) {
+        while let Some(input) = window.next_input() {
+            match input {
+                window::Input::Press(button) => self.press(button, slot, rewinding),
+                window::Input::Release(button) => self.release(button, rewinding)
+            }
+        }
+
+        self.apply_input();
+    }
+
+    fn press(&mut self, button: Button, slot: &mut u8, rewinding: &mut bool) {
+        match button {
             Button::Keyboard(Key::Escape) => { self.state = State::Quit }
 
-            Button::Keyboard(Key::D1) => { self.keypad[0x1] = true; }
-            Button::Keyboard(Key::D2) => { self.keypad[0x2] = true; }
-            Button::Keyboard(Key::D3) => { self.keypad[0x3] = true; }
-            Button::Keyboard(Key::D4) => { self.keypad[0xC] = true; }
-
-            Button::Keyboard(Key::Q) => { self.keypad[0x4] = true; }
-            Button::Keyboard(Key::W) => { self.keypad[0x5] = true; }
-            Button::Keyboard(Key::E) => { self.keypad[0x6] = true; }
-            Button::Keyboard(Key::R) => { self.keypad[0xD] = true; }
-
-            Button::Keyboard(Key::A) => { self.keypad[0x7] = true; }
-            Button::Keyboard(Key::S) => { self.keypad[0x8] = true; }
-            Button::Keyboard(Key::D) => { self.keypad[0x9] = true; }
-            Button::Keyboard(Key::F) => { self.keypad[0xE] = true; }
-
-            Button::Keyboard(Key::Z) => { self.keypad[0xA] = true; }
-            Button::Keyboard(Key::X) => { self.keypad[0x0] = true; }
-            Button::Keyboard(Key::C) => { self.keypad[0xB] = true; }
-            Button::Keyboard(Key::V) => { self.keypad[0xF] = true; }
+            // a key bound in the keymap is not a hotkey
+            Button::Keyboard(key) if self.is_bound(key) => self.queue_key(&format!("{:?}", key), true),
+
+            Button::Keyboard(Key::F5) => {
+                self.state = match self.state {
+                    State::Running => State::Paused,
+                    State::Paused => State::Running,
+                    state => state
+                };
+            }
+            Button::Keyboard(Key::F6) => match self.save_slot(*slot) {
+                Ok(path) => eprintln!("saved state to {}", path.display()),
+                Err(err) => eprintln!("failed to save state: {}", err)
+            },
+            Button::Keyboard(Key::F7) => match self.load_slot(*slot) {
+                Ok(path) => eprintln!("loaded state from {}", path.display()),
+                Err(err) => eprintln!("failed to load state: {}", err)
+            },
+            Button::Keyboard(Key::F8) => {
+                *slot = (*slot + 1) % savestate::SLOTS;
+                eprintln!("save state slot {}", slot);
+            }
+            Button::Keyboard(Key::Backspace) => { *rewinding = true; }
+            Button::Keyboard(Key::F10) if self.state == State::Paused => {
+                let stop: Result<debugger::Stop, Chip8Error> = self.step_over();
+                self.report_stop(stop);
+            }
+            Button::Keyboard(Key::F11) if self.state == State::Paused => {
+                let stop: Result<debugger::Stop, Chip8Error> = self.step();
+                self.report_stop(stop);
+            }
+
+            Button::Keyboard(key) => self.queue_key(&format!("{:?}", key), true),
             _ => ()
         }
+    }
 
-        match self.window.get_released_key() {
-            Button::Keyboard(Key::D1) => { self.keypad[0x1] = false; }
-            Button::Keyboard(Key::D2) => { self.keypad[0x2] = false; }
-            Button::Keyboard(Key::D3) => { self.keypad[0x3] = false; }
-            Button::Keyboard(Key::D4) => { self.keypad[0xC] = false; }
-
-            Button::Keyboard(Key::Q) => { self.keypad[0x4] = false; }
-            Button::Keyboard(Key::W) => { self.keypad[0x5] = false; }
-            Button::Keyboard(Key::E) => { self.keypad[0x6] = false; }
-            Button::Keyboard(Key::R) => { self.keypad[0xD] = false; }
+    fn is_bound(&self, key: Key) -> bool {
+        self.keymap.key(&format!("{:?}", key)).is_some()
+    }
 
-            Button::Keyboard(Key::A) => { self.keypad[0x7] = false; }
-            Button::Keyboard(Key::S) => { self.keypad[0x8] = false; }
-            Button::Keyboard(Key::D) => { self.keypad[0x9] = false; }
-            Button::Keyboard(Key::F) => { self.keypad[0xE] = false; }
+    fn release(&mut self, button: Button, rewinding: &mut bool) {
+        match button {
+            Button::Keyboard(key) if self.is_bound(key) => self.queue_key(&format!("{:?}", key), false),
+            Button::Keyboard(Key::Backspace) => { *rewinding = false; }
 
-            Button::Keyboard(Key::Z) => { self.keypad[0xA] = false; }
-            Button::Keyboard(Key::X) => { self.keypad[0x0] = false; }
-            Button::Keyboard(Key::C) => { self.keypad[0xB] = false; }
-            Button::Keyboard(Key::V) => { self.keypad[0xF] = false; }
+            Button::Keyboard(key) => self.queue_key(&format!("{:?}", key), false),
             _ => ()
         }
     }
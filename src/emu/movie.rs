@@ -0,0 +1,283 @@
+//! Input movies for reproducing a run.
+//!
+//! A movie holds everything a run depends on besides the rom: the random
+//! seed and generator, quirks, speed, memory size and the keypad state of every frame, plus
+//! hashes of the final framebuffer and memory to verify a replay against.
+//! The text format is line based, keypad states are run-length encoded:
+//!
+//! ```text
+//! chip8-movie 1
+//! rom 5d2c6f0a93b1e7c4
+//! seed 00000000000004d2
+//! rng xorshift
+//! memory 4096
+//! quirks shift=0 vf-reset=1 memory-increment=x+1 jump-with-vx=0 wrap=0 display-wait=1 stack-depth=12
+//! speed 8
+//! input 0000 120
+//! input 0020 4
+//! display 1f0c9a7e55d2b830
+//! ram 8e42d1c07a6b93f5
+//! ```
+
+use std::{
+    fmt,
+    fs,
+    str::FromStr
+};
+use super::{
+    Machine,
+    ram
+};
+use super::cpu::INSTRUCTIONS_PER_FRAME;
+use super::cpu::quirks::{
+    self,
+    MemoryIncrement,
+    Quirks
+};
+use super::cpu::rng::{
+    Algorithm,
+    Rng
+};
+use super::error::Chip8Error;
+
+pub const VERSION: u32 = 1;
+
+
+/// 64-bit FNV-1a, used for rom, framebuffer and memory hashes.
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub rng: Rng,               // CXNN generator at power-on
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    pub memory_size: usize,
+    pub frames: Vec<u16>,       // keypad of every frame, bit N is key N
+    pub display_hash: u64,      // framebuffer after the last frame
+    pub ram_hash: u64           // memory after the last frame
+}
+
+
+impl Movie {
+    /// Empty movie starting from the power-on state of `machine`.
+    pub fn new(machine: &Machine) -> Self {
+        Self {
+            rom_hash: machine.rom_hash,
+            seed: machine.cpu.seed(),
+            rng: machine.cpu.rng().clone(),
+            quirks: *machine.cpu.quirks(),
+            instructions_per_frame: machine.cpu.instructions_per_frame(),
+            memory_size: machine.ram.size(),
+            frames: Vec::new(),
+            display_hash: 0,
+            ram_hash: 0
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, Chip8Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Chip8Error> {
+        fs::write(path, self.to_string())?;
+
+        Ok(())
+    }
+
+    /// Stores the final hashes of `machine`.
+    pub fn finish(&mut self, machine: &Machine) {
+        self.display_hash = hash(machine.display.pixels());
+        self.ram_hash = hash(machine.ram.space());
+    }
+
+    /// Runs the movie on `rom` and checks that it ends with the recorded
+    /// framebuffer and memory. Returns the machine after the last frame.
+    pub fn replay(&self, rom: &[u8]) -> Result<Machine, Chip8Error> {
+        Self::check("rom", self.rom_hash, hash(rom))?;
+
+        let mut machine: Machine = Machine::with_quirks(self.quirks);
+
+        machine.ram = ram::RAM::with_size(self.memory_size);
+        machine.set_seed(self.seed);
+        machine.set_rng(self.rng.clone());
+        machine.set_instructions_per_frame(self.instructions_per_frame);
+        machine.init_from_bytes(rom)?;
+
+        for keys in self.frames.iter() {
+            for key in 0..16 {
+                machine.set_key(key, keys & (1 << key) != 0);
+            }
+
+            machine.step_frame()?;
+        }
+
+        Self::check("display", self.display_hash, hash(machine.display.pixels()))?;
+        Self::check("ram", self.ram_hash, hash(machine.ram.space()))?;
+
+        Ok(machine)
+    }
+
+    fn check(what: &'static str, expected: u64, actual: u64) -> Result<(), Chip8Error> {
+        if expected != actual {
+            return Err(Chip8Error::MovieMismatch { what, expected, actual });
+        }
+
+        Ok(())
+    }
+
+    fn memory_increment_name(increment: MemoryIncrement) -> &'static str {
+        match increment {
+            MemoryIncrement::Unchanged => "unchanged",
+            MemoryIncrement::ByX => "x",
+            MemoryIncrement::ByXPlusOne => "x+1"
+        }
+    }
+
+    fn parse_quirks(fields: &[&str], line: usize) -> Result<Quirks, Chip8Error> {
+        let bad = |reason: &'static str| Chip8Error::BadMovie { line, reason };
+        let mut quirks: Quirks = Quirks::default();
+
+        for field in fields {
+            let (name, value) = field.split_once('=').ok_or(bad("quirk must be name=value"))?;
+
+            // quirks added by later versions are ignored
+            if quirks::NAMES.contains(&name) {
+                quirks.set(name, value).map_err(|_| bad("bad quirk value"))?;
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "chip8-movie {}", VERSION)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+        writeln!(f, "seed {:016x}", self.seed)?;
+
+        match (&self.rng, self.rng.algorithm()) {
+            (_, Some(algorithm)) => writeln!(f, "rng {}", algorithm)?,
+            (Rng::Scripted { values, .. }, None) => {
+                let hex: String = values.iter().map(|value| format!("{:02x}", value)).collect();
+                writeln!(f, "rng scripted {}", hex)?;
+            }
+            _ => ()
+        }
+
+        writeln!(f, "memory {}", self.memory_size)?;
+        writeln!(
+            f,
+            "quirks shift={} vf-reset={} memory-increment={} jump-with-vx={} wrap={} display-wait={} stack-depth={}",
+            self.quirks.shift as u8,
+            self.quirks.vf_reset as u8,
+            Self::memory_increment_name(self.quirks.memory_increment),
+            self.quirks.jump_with_vx as u8,
+            self.quirks.wrap as u8,
+            self.quirks.display_wait as u8,
+            self.quirks.stack_depth
+        )?;
+        writeln!(f, "speed {}", self.instructions_per_frame)?;
+
+        let mut frames = self.frames.iter().peekable();
+
+        while let Some(keys) = frames.next() {
+            let mut count: usize = 1;
+
+            while frames.next_if_eq(&keys).is_some() {
+                count += 1;
+            }
+
+            writeln!(f, "input {:04x} {}", keys, count)?;
+        }
+
+        writeln!(f, "display {:016x}", self.display_hash)?;
+        writeln!(f, "ram {:016x}", self.ram_hash)
+    }
+}
+
+
+impl FromStr for Movie {
+    type Err = Chip8Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line));
+
+        match lines.next() {
+            Some((_, header)) if header.trim() == format!("chip8-movie {}", VERSION) => (),
+            _ => return Err(Chip8Error::BadMovie { line: 1, reason: "not a version 1 movie" })
+        }
+
+        // the generator is built once the seed is known
+        let mut algorithm: Algorithm = Algorithm::default();
+        let mut script: Option<Vec<u8>> = None;
+        let mut movie: Movie = Movie {
+            rom_hash: 0,
+            seed: 0,
+            rng: Rng::default(),
+            quirks: Quirks::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            memory_size: 0x1000,
+            frames: Vec::new(),
+            display_hash: 0,
+            ram_hash: 0
+        };
+
+        for (line, text) in lines {
+            let bad = |reason: &'static str| Chip8Error::BadMovie { line, reason };
+            let hex = |value: &str| u64::from_str_radix(value, 16).map_err(|_| bad("bad hex number"));
+            let fields: Vec<&str> = text.split_whitespace().collect();
+
+            match fields.as_slice() {
+                [] => (),
+                ["rom", value] => movie.rom_hash = hex(value)?,
+                ["seed", value] => movie.seed = hex(value)?,
+                ["display", value] => movie.display_hash = hex(value)?,
+                ["ram", value] => movie.ram_hash = hex(value)?,
+                ["memory", value] => {
+                    movie.memory_size = value
+                        .parse()
+                        .ok()
+                        .filter(|size| (ram::MIN_SIZE..=ram::MAX_SIZE).contains(size))
+                        .ok_or(bad("bad memory size"))?;
+                }
+                ["rng", "scripted", values] => {
+                    let values: Vec<u8> = (0..values.len())
+                        .step_by(2)
+                        .map(|at| values.get(at..at + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+                        .collect::<Option<_>>()
+                        .ok_or(bad("bad scripted random values"))?;
+
+                    script = Some(values);
+                }
+                ["rng", name] => algorithm = name.parse().map_err(|_| bad("unknown random generator"))?,
+                ["speed", value] => {
+                    movie.instructions_per_frame = value.parse().map_err(|_| bad("bad instructions per frame"))?;
+                }
+                ["quirks", quirks @ ..] => movie.quirks = Self::parse_quirks(quirks, line)?,
+                ["input", keys, count] => {
+                    let keys: u16 = u16::from_str_radix(keys, 16).map_err(|_| bad("bad keypad state"))?;
+                    let count: usize = count.parse().map_err(|_| bad("bad frame count"))?;
+
+                    movie.frames.extend(std::iter::repeat_n(keys, count));
+                }
+                _ => return Err(bad("unknown line"))
+            }
+        }
+
+        movie.rng = match script {
+            Some(values) => Rng::scripted(values),
+            None => Rng::new(algorithm, movie.seed)
+        };
+
+        Ok(movie)
+    }
+}
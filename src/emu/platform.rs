@@ -0,0 +1,63 @@
+use std::{
+    fmt,
+    str::FromStr
+};
+use super::cpu::quirks::Quirks;
+
+
+/// Interpreter family a rom was written for.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Platform {
+    #[default]
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip
+}
+
+
+impl Platform {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks::cosmac_vip(),
+            Platform::Chip48 => Quirks::chip48(),
+            Platform::SuperChip => Quirks::superchip(),
+            Platform::XoChip => Quirks::octo()
+        }
+    }
+
+    /// Addressable memory in bytes.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000
+        }
+    }
+}
+
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "vip" | "cosmac-vip" | "chip8" => Ok(Platform::CosmacVip),
+            "chip48" => Ok(Platform::Chip48),
+            "schip" | "superchip" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Err(format!("unknown platform '{}', expected vip, chip48, schip or xochip", name))
+        }
+    }
+}
+
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Platform::CosmacVip => write!(f, "vip"),
+            Platform::Chip48 => write!(f, "chip48"),
+            Platform::SuperChip => write!(f, "schip"),
+            Platform::XoChip => write!(f, "xochip")
+        }
+    }
+}
@@ -1,50 +1,164 @@
 use std::{
+    cell::RefCell,
     io::Read,
     fs::File
 };
 use super::cpu;
+use super::debugger::watch::{
+    Access,
+    AccessKind,
+    Location
+};
+use super::error::Chip8Error;
+use super::savestate::{
+    Reader,
+    Writer
+};
+
+pub mod font;
 
-mod font;
+/// Everything below the entry point plus one instruction.
+pub const MIN_SIZE: usize = cpu::ENTRY_POINT as usize + 2;
+/// The 64 KiB of XO-CHIP, the largest platform memory.
+pub const MAX_SIZE: usize = 0x10000;
 
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct RAM {
-    space: [u8; 0x1000]
+    space: Vec<u8>,
+    reporting: bool,                    // record accesses for watchpoints
+    accesses: RefCell<Vec<Access>>      // reads are recorded through a shared reference
+}
+
+
+impl Default for RAM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 
 impl RAM {
     pub fn new() -> Self {
+        Self::with_size(0x1000)
+    }
+
+    /// 4 KiB for classic interpreters, 64 KiB for XO-CHIP.
+    ///
+    /// Panics when `size` leaves no room for a rom, below [`MIN_SIZE`].
+    pub fn with_size(size: usize) -> Self {
+        assert!(size >= MIN_SIZE, "memory of {} bytes is smaller than the minimum of {}", size, MIN_SIZE);
+
         Self {
-            space: [0u8; 0x1000]
+            space: vec![0u8; size],
+            reporting: false,
+            accesses: RefCell::new(Vec::new())
         }
     }
 
-    pub fn read(&self, addr: usize) -> u8 {
-        self.space[addr]
+    pub fn size(&self) -> usize {
+        self.space.len()
     }
 
-    pub fn write(&mut self, addr: usize, val: u8) {
-        self.space[addr] = val;
+    pub fn read(&self, addr: usize) -> Result<u8, Chip8Error> {
+        let val: u8 = self.peek(addr)?;
+
+        if self.reporting {
+            self.accesses.borrow_mut().push(Access {
+                location: Location::Memory(addr),
+                kind: AccessKind::Read,
+                value: val as u16
+            });
+        }
+
+        Ok(val)
+    }
+
+    /// Reads without reporting the access, used for instruction fetch.
+    pub fn peek(&self, addr: usize) -> Result<u8, Chip8Error> {
+        self.space
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::OutOfBounds { addr })
+    }
+
+    pub fn write(&mut self, addr: usize, val: u8) -> Result<(), Chip8Error> {
+        let cell: &mut u8 = self.space
+            .get_mut(addr)
+            .ok_or(Chip8Error::OutOfBounds { addr })?;
+
+        *cell = val;
+
+        if self.reporting {
+            self.accesses.get_mut().push(Access {
+                location: Location::Memory(addr),
+                kind: AccessKind::Write,
+                value: val as u16
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Turns recording of reads and writes on or off, recorded accesses are dropped.
+    pub fn set_reporting(&mut self, reporting: bool) {
+        self.reporting = reporting;
+        self.accesses.get_mut().clear();
+    }
+
+    /// Accesses recorded since the last call.
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(self.accesses.get_mut())
     }
 
     fn load_font(&mut self) {
-        self.space[0..font::FONT_SET.len()].copy_from_slice(&font::FONT_SET);
+        self.space[font::FONT_ADDR..font::FONT_ADDR + font::FONT_SET.len()]
+            .copy_from_slice(&font::FONT_SET);
+        self.space[font::BIG_FONT_ADDR..font::BIG_FONT_ADDR + font::BIG_FONT_SET.len()]
+            .copy_from_slice(&font::BIG_FONT_SET);
+    }
+
+    pub fn load_rom(&mut self, rom_path: String) -> Result<(), Chip8Error> {
+        let mut rom: Vec<u8> = Vec::new();
+
+        File::open(rom_path)?.read_to_end(&mut rom)?;
+
+        self.load_rom_bytes(&rom)
     }
 
-    pub fn load_rom(&mut self, rom_path: String) {
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let begin: usize = cpu::ENTRY_POINT as usize;
+        let max_size: usize = self.space.len() - begin;
+
+        if rom.len() > max_size {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max_size });
+        }
+
         self.load_font();
+        self.space[begin..begin + rom.len()].copy_from_slice(rom);
 
-        let mut rom_file: File = File::open(rom_path).unwrap();
+        Ok(())
+    }
 
-        let rom_size: usize = rom_file
-            .read(&mut self.space[cpu::ENTRY_POINT as usize..])
-            .unwrap();
+    pub fn space(&self) -> &[u8] {
+        &self.space
+    }
+
+    pub fn save_state(&self, w: &mut Writer) {
+        w.bytes(&self.space);
+    }
 
-        let max_size: usize = self.space.len() - cpu::ENTRY_POINT as usize;
+    pub fn from_state(r: &mut Reader) -> Result<Self, Chip8Error> {
+        let space: Vec<u8> = r.bytes()?.to_vec();
 
-        if rom_size > max_size {
-            panic!("What the fuck?! Why does this rom filesize is bigger than RAM!??\n
-                    Are you trying to load GTA V on chip-8 emulator or what?")
+        if space.len() < MIN_SIZE {
+            return Err(Chip8Error::BadSaveState { reason: "memory is too small" });
         }
+
+        Ok(Self {
+            space,
+            reporting: false,
+            accesses: RefCell::new(Vec::new())
+        })
     }
 }
@@ -0,0 +1,159 @@
+//! Frame history for running a game backwards.
+//!
+//! Only the newest save state is kept whole. Every older frame is stored as
+//! the XOR against its successor with runs of unchanged bytes collapsed, so
+//! a frame where little happens costs a handful of bytes.
+
+use std::collections::VecDeque;
+
+/// Five minutes at 60hz.
+pub const DEFAULT_FRAMES: usize = 5 * 60 * 60;
+
+
+pub struct Rewind {
+    capacity: usize,            // frames that can be stepped back
+    deltas: VecDeque<Vec<u8>>,  // oldest first, each turns a state into its predecessor
+    current: Option<Vec<u8>>    // newest recorded state
+}
+
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_FRAMES)
+    }
+}
+
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            deltas: VecDeque::new(),
+            current: None
+        }
+    }
+
+    /// Number of frames that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Bytes used by the history.
+    pub fn memory_usage(&self) -> usize {
+        self.deltas.iter().map(Vec::len).sum::<usize>() + self.current.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.current = None;
+    }
+
+    /// Records the state of a new frame, dropping the oldest one when full.
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.current.take() {
+            self.deltas.push_back(Self::encode(&state, &previous));
+
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+
+        self.current = Some(state);
+    }
+
+    /// Steps one frame back and returns that state, None when the history is exhausted.
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta: Vec<u8> = self.deltas.pop_back()?;
+        let current: &mut Vec<u8> = self.current.as_mut()?;
+
+        Self::apply(current, &delta);
+
+        Some(current)
+    }
+
+    // layout: length of `older`, then pairs of (unchanged run, changed run, changed bytes)
+    fn encode(newer: &[u8], older: &[u8]) -> Vec<u8> {
+        let len: usize = newer.len().max(older.len());
+        let xor = |at: usize| newer.get(at).copied().unwrap_or(0) ^ older.get(at).copied().unwrap_or(0);
+        let mut delta: Vec<u8> = Vec::new();
+        let mut at: usize = 0;
+
+        Self::write_varint(&mut delta, older.len());
+
+        while at < len {
+            let same_begin: usize = at;
+
+            while at < len && xor(at) == 0 {
+                at += 1;
+            }
+
+            let changed_begin: usize = at;
+
+            while at < len && xor(at) != 0 {
+                at += 1;
+            }
+
+            Self::write_varint(&mut delta, changed_begin - same_begin);
+            Self::write_varint(&mut delta, at - changed_begin);
+            delta.extend((changed_begin..at).map(xor));
+        }
+
+        delta
+    }
+
+    fn apply(state: &mut Vec<u8>, delta: &[u8]) {
+        let mut pos: usize = 0;
+        let older_len: usize = Self::read_varint(delta, &mut pos);
+        let mut at: usize = 0;
+
+        while pos < delta.len() {
+            at += Self::read_varint(delta, &mut pos);
+
+            let changed: usize = Self::read_varint(delta, &mut pos);
+
+            if state.len() < at + changed {
+                state.resize(at + changed, 0);
+            }
+
+            for byte in state[at..at + changed].iter_mut() {
+                *byte ^= delta[pos];
+                pos += 1;
+            }
+
+            at += changed;
+        }
+
+        state.resize(older_len, 0);
+    }
+
+    fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+
+        out.push(value as u8);
+    }
+
+    fn read_varint(bytes: &[u8], pos: &mut usize) -> usize {
+        let mut value: usize = 0;
+        let mut shift: u32 = 0;
+
+        while let Some(byte) = bytes.get(*pos) {
+            *pos += 1;
+            value |= ((byte & 0x7F) as usize) << shift;
+
+            if byte & 0x80 == 0 {
+                break;
+            }
+
+            shift += 7;
+        }
+
+        value
+    }
+}
@@ -0,0 +1,203 @@
+//! Binary save state format.
+//!
+//! A state starts with the magic `C8ST` and a little-endian u16 format
+//! version, followed by chunks of a 4 byte tag, a u32 length and the payload.
+//! Readers skip chunks they do not know and ignore bytes after the fields
+//! they understand, so newer versions only ever add chunks or append fields.
+//! The version is only bumped for changes older readers cannot skip.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf
+};
+use super::error::Chip8Error;
+
+pub const MAGIC: &[u8; 4] = b"C8ST";
+pub const VERSION: u16 = 1;
+pub const SLOTS: u8 = 10;
+
+pub type Tag = [u8; 4];
+
+
+/// File of a save slot, next to the rom.
+pub fn slot_path(rom_name: &str, slot: u8) -> PathBuf {
+    PathBuf::from(format!("{}.state{}", rom_name, slot))
+}
+
+
+pub struct Writer {
+    bytes: Vec<u8>
+}
+
+
+impl Default for Writer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Writer {
+    pub fn new() -> Self {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+        Self { bytes }
+    }
+
+    /// Writes a chunk whose payload is produced by `write`.
+    pub fn chunk<F: FnOnce(&mut Writer)>(&mut self, tag: &Tag, write: F) {
+        self.bytes.extend_from_slice(tag);
+
+        let length_at: usize = self.bytes.len();
+
+        self.u32(0);
+        write(self);
+
+        let length: u32 = (self.bytes.len() - length_at - 4) as u32;
+        self.bytes[length_at..length_at + 4].copy_from_slice(&length.to_le_bytes());
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Length prefixed byte string.
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+
+/// Reads the fields of one chunk.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Chip8Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let bytes: &[u8] = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let mut bytes: [u8; 4] = [0; 4];
+
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Chip8Error> {
+        let mut bytes: [u8; 8] = [0; 8];
+
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, Chip8Error> {
+        let mut bytes: [u8; 8] = [0; 8];
+
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], Chip8Error> {
+        let length: usize = self.u32()? as usize;
+        self.take(length)
+    }
+
+    /// Byte string that must have exactly `N` bytes.
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], Chip8Error> {
+        self.bytes()?
+            .try_into()
+            .map_err(|_| Chip8Error::BadSaveState { reason: "field has the wrong size" })
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Chip8Error> {
+        let end: usize = self.pos + count;
+
+        let bytes: &[u8] = self.bytes
+            .get(self.pos..end)
+            .ok_or(Chip8Error::BadSaveState { reason: "chunk is truncated" })?;
+
+        self.pos = end;
+
+        Ok(bytes)
+    }
+}
+
+
+/// Checks the header and splits a state into its chunks.
+pub fn chunks(state: &[u8]) -> Result<HashMap<Tag, &[u8]>, Chip8Error> {
+    if state.len() < 6 || &state[..4] != MAGIC {
+        return Err(Chip8Error::BadSaveState { reason: "not a save state" });
+    }
+
+    if u16::from_le_bytes([state[4], state[5]]) != VERSION {
+        return Err(Chip8Error::BadSaveState { reason: "unsupported save state version" });
+    }
+
+    let mut chunks: HashMap<Tag, &[u8]> = HashMap::new();
+    let mut reader: Reader = Reader::new(&state[6..]);
+
+    while reader.pos < reader.bytes.len() {
+        let tag: Tag = reader
+            .take(4)?
+            .try_into()
+            .map_err(|_| Chip8Error::BadSaveState { reason: "chunk is truncated" })?;
+        let length: usize = reader.u32()? as usize;
+
+        chunks.insert(tag, reader.take(length)?);
+    }
+
+    Ok(chunks)
+}
+
+
+/// Reader over the chunk with `tag`, which the state must contain.
+pub fn chunk<'a>(chunks: &HashMap<Tag, &'a [u8]>, tag: &Tag) -> Result<Reader<'a>, Chip8Error> {
+    chunks
+        .get(tag)
+        .map(|bytes| Reader::new(bytes))
+        .ok_or(Chip8Error::BadSaveState { reason: "chunk is missing" })
+}
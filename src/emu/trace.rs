@@ -0,0 +1,494 @@
+//! Instruction traces for diffing runs against other emulators.
+//!
+//! Every entry holds the machine state *before* the instruction executes,
+//! entries are only recorded for instructions that executed without a fault.
+//! The text format is one line per instruction, laid out by a [`TraceFormat`]
+//! template so the lines can match the log of the emulator being compared
+//! against. `{name}` is replaced by a field of the entry, `{{` and `}}` are
+//! literal braces:
+//!
+//! | field            | value                                          |
+//! |------------------|------------------------------------------------|
+//! | `cycle`          | instructions executed before, 8 decimal digits |
+//! | `pc`, `op`, `i`  | 4 hex digits                                   |
+//! | `v0` to `vf`     | 2 hex digits                                   |
+//! | `regs`           | `V0:00 V1:00 .. VF:00`                         |
+//! | `dt`, `st`       | timers, 2 hex digits                           |
+//! | `sp`             | stack pointer in hex                           |
+//! | `octo`, `classic`| disassembly in Octo or classic syntax          |
+//!
+//! The default is [`DEFAULT_FORMAT`]:
+//!
+//! ```text
+//! 00000000 PC:0200 OP:6001 V0:00 V1:00 .. VF:00 I:0000 DT:00 ST:00 SP:0 v0 := 0x01
+//! ```
+//!
+//! The binary format starts with the magic `C8TR` and a version byte,
+//! followed by fixed size little-endian records of [`RECORD_SIZE`] bytes.
+
+use std::{
+    fmt,
+    fs::File,
+    str::FromStr,
+    io::{
+        self,
+        BufWriter,
+        Read,
+        Write
+    }
+};
+use super::cpu::instruction::{
+    Op,
+    Syntax
+};
+
+pub const MAGIC: &[u8; 4] = b"C8TR";
+pub const VERSION: u8 = 1;
+pub const RECORD_SIZE: usize = 35;
+
+pub const DEFAULT_FORMAT: &str = "{cycle} PC:{pc} OP:{op} {regs} I:{i} DT:{dt} ST:{st} SP:{sp} {octo}";
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TraceEntry {
+    pub cycle: u64,         // instructions executed before this one
+    pub pc: u16,
+    pub opcode: u16,
+    pub next: u16,          // second word, only meaningful for F000 NNNN
+    pub v: [u8; 16],
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub sp: u8
+}
+
+
+impl TraceEntry {
+    pub fn op(&self) -> Op {
+        Op::decode(self.opcode, self.next)
+    }
+
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+
+        bytes[0..8].copy_from_slice(&self.cycle.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.pc.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.opcode.to_le_bytes());
+        bytes[12..14].copy_from_slice(&self.next.to_le_bytes());
+        bytes[14..30].copy_from_slice(&self.v);
+        bytes[30..32].copy_from_slice(&self.i.to_le_bytes());
+        bytes[32] = self.delay_timer;
+        bytes[33] = self.sound_timer;
+        bytes[34] = self.sp;
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Self {
+        let word = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let mut cycle: [u8; 8] = [0; 8];
+        let mut v: [u8; 16] = [0; 16];
+
+        cycle.copy_from_slice(&bytes[0..8]);
+        v.copy_from_slice(&bytes[14..30]);
+
+        Self {
+            cycle: u64::from_le_bytes(cycle),
+            pc: word(8),
+            opcode: word(10),
+            next: word(12),
+            v,
+            i: word(30),
+            delay_timer: bytes[32],
+            sound_timer: bytes[33],
+            sp: bytes[34]
+        }
+    }
+}
+
+
+impl fmt::Display for TraceEntry {
+    /// Writes the entry in the [`DEFAULT_FORMAT`].
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        TraceFormat::default().write(f, self)
+    }
+}
+
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Field {
+    Cycle,
+    Pc,
+    Opcode,
+    V(usize),
+    Registers,      // every V register with its name
+    I,
+    DelayTimer,
+    SoundTimer,
+    Sp,
+    Disassembly(Syntax)
+}
+
+
+#[derive(PartialEq, Debug, Clone)]
+enum Part {
+    Text(String),
+    Field(Field)
+}
+
+
+/// Layout of a text trace line, parsed from a template such as [`DEFAULT_FORMAT`].
+#[derive(PartialEq, Debug, Clone)]
+pub struct TraceFormat {
+    parts: Vec<Part>
+}
+
+
+impl Default for TraceFormat {
+    fn default() -> Self {
+        DEFAULT_FORMAT.parse().expect("the default trace format is valid")
+    }
+}
+
+
+impl TraceFormat {
+    pub fn write<W: fmt::Write>(&self, w: &mut W, entry: &TraceEntry) -> fmt::Result {
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => w.write_str(text)?,
+                Part::Field(Field::Cycle) => write!(w, "{:08}", entry.cycle)?,
+                Part::Field(Field::Pc) => write!(w, "{:04X}", entry.pc)?,
+                Part::Field(Field::Opcode) => write!(w, "{:04X}", entry.opcode)?,
+                Part::Field(Field::V(x)) => write!(w, "{:02X}", entry.v[*x])?,
+                Part::Field(Field::Registers) => {
+                    let registers: Vec<String> = entry.v
+                        .iter()
+                        .enumerate()
+                        .map(|(x, value)| format!("V{:X}:{:02X}", x, value))
+                        .collect();
+
+                    w.write_str(&registers.join(" "))?;
+                }
+                Part::Field(Field::I) => write!(w, "{:04X}", entry.i)?,
+                Part::Field(Field::DelayTimer) => write!(w, "{:02X}", entry.delay_timer)?,
+                Part::Field(Field::SoundTimer) => write!(w, "{:02X}", entry.sound_timer)?,
+                Part::Field(Field::Sp) => write!(w, "{:X}", entry.sp)?,
+                Part::Field(Field::Disassembly(syntax)) => write!(w, "{}", entry.op().disassemble(*syntax))?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn field(name: &str) -> Option<Field> {
+        let field: Field = match name {
+            "cycle" => Field::Cycle,
+            "pc" => Field::Pc,
+            "op" => Field::Opcode,
+            "regs" => Field::Registers,
+            "i" => Field::I,
+            "dt" => Field::DelayTimer,
+            "st" => Field::SoundTimer,
+            "sp" => Field::Sp,
+            "octo" => Field::Disassembly(Syntax::Octo),
+            "classic" => Field::Disassembly(Syntax::Classic),
+            _ => {
+                let x: &str = name.strip_prefix('v').filter(|x| x.len() == 1)?;
+
+                Field::V(usize::from_str_radix(x, 16).ok()?)
+            }
+        };
+
+        Some(field)
+    }
+}
+
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<Part> = Vec::new();
+        let mut text: String = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or(format!("unclosed '{{' in trace format '{}'", template))?;
+                    let field: Field = Self::field(name).ok_or(format!("unknown trace field '{}'", name))?;
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+
+                    parts.push(Part::Field(field));
+                    chars = rest.chars();
+                }
+                '}' => return Err(format!("unmatched '}}' in trace format '{}'", template)),
+                c => text.push(c)
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+
+/// Receives every instruction that executed, with the state from before it ran.
+pub trait TraceSink {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()>;
+}
+
+
+pub struct TextTrace<W: Write> {
+    writer: W,
+    format: TraceFormat,
+    line: String        // reused for every entry
+}
+
+
+impl TextTrace<BufWriter<File>> {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+
+impl<W: Write> TextTrace<W> {
+    /// Trace in the [`DEFAULT_FORMAT`].
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            format: TraceFormat::default(),
+            line: String::new()
+        }
+    }
+
+    pub fn with_format(mut self, format: TraceFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Flushes the trace and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+
+impl<W: Write> TraceSink for TextTrace<W> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        self.line.clear();
+        self.format
+            .write(&mut self.line, entry)
+            .map_err(|_| io::Error::other("failed to format a trace entry"))?;
+
+        writeln!(self.writer, "{}", self.line)
+    }
+}
+
+
+pub struct BinaryTrace<W: Write> {
+    writer: W
+}
+
+
+impl BinaryTrace<BufWriter<File>> {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+
+impl<W: Write> BinaryTrace<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        Ok(Self { writer })
+    }
+
+    /// Flushes the trace and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+
+impl<W: Write> TraceSink for BinaryTrace<W> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        self.writer.write_all(&entry.to_bytes())
+    }
+}
+
+
+/// Reads a whole binary trace, e.g. to print it in the text format.
+pub fn read_binary<R: Read>(mut reader: R) -> io::Result<Vec<TraceEntry>> {
+    let mut header: [u8; 5] = [0; 5];
+
+    reader.read_exact(&mut header)?;
+
+    if &header[..4] != MAGIC || header[4] != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a version 1 chip-8 trace"));
+    }
+
+    let mut entries: Vec<TraceEntry> = Vec::new();
+    let mut record: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => entries.push(TraceEntry::from_bytes(&record)),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(entries),
+            Err(err) => return Err(err)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        rc::Rc
+    };
+    use super::*;
+    use super::super::{
+        Machine,
+        asm,
+        error::FaultPolicy
+    };
+
+    const ENTRY: TraceEntry = TraceEntry {
+        cycle: 0x0102_0304_0506_0708,
+        pc: 0x0ABC,
+        opcode: 0xF000,
+        next: 0x1234,
+        v: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF],
+        i: 0xFFFE,
+        delay_timer: 0x3C,
+        sound_timer: 0x01,
+        sp: 0xB
+    };
+
+
+    #[derive(Clone, Default)]
+    struct Entries(Rc<RefCell<Vec<TraceEntry>>>);
+
+
+    impl TraceSink for Entries {
+        fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+            self.0.borrow_mut().push(*entry);
+            Ok(())
+        }
+    }
+
+
+    fn line(template: &str) -> String {
+        let format: TraceFormat = template.parse().unwrap();
+        let mut line: String = String::new();
+
+        format.write(&mut line, &ENTRY).unwrap();
+        line
+    }
+
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(line("{{pc}} {pc} }}{{"), "{pc} 0ABC }{");
+    }
+
+
+    #[test]
+    fn single_registers_by_hex_digit() {
+        assert_eq!(line("{v0}/{v9}/{va}/{vF}"), "00/09/0A/0F");
+        assert_eq!(line("{cycle} {i} {dt} {st} {sp}"), "72623859790382856 FFFE 3C 01 B");
+    }
+
+
+    #[test]
+    fn bad_templates_are_rejected() {
+        for (template, error) in [
+            ("{pc", "unclosed '{'"),
+            ("pc}", "unmatched '}'"),
+            ("{pc} {vg}", "unknown trace field 'vg'"),
+            ("{v10}", "unknown trace field 'v10'"),
+            ("{}", "unknown trace field ''")
+        ] {
+            let err: String = template.parse::<TraceFormat>().unwrap_err();
+
+            assert!(err.contains(error), "{}: {}", template, err);
+        }
+    }
+
+
+    #[test]
+    fn binary_records_round_trip() {
+        let mut trace: BinaryTrace<Vec<u8>> = BinaryTrace::new(Vec::new()).unwrap();
+        let second: TraceEntry = TraceEntry { cycle: 1, opcode: 0x6001, next: 0, ..ENTRY };
+
+        assert_eq!(TraceEntry::from_bytes(&ENTRY.to_bytes()), ENTRY);
+
+        trace.record(&ENTRY).unwrap();
+        trace.record(&second).unwrap();
+
+        let bytes: Vec<u8> = trace.finish().unwrap();
+
+        assert_eq!(bytes.len(), 5 + 2 * RECORD_SIZE);
+        assert_eq!(read_binary(&bytes[..]).unwrap(), [ENTRY, second]);
+    }
+
+
+    #[test]
+    fn binary_header_is_checked() {
+        let mut bytes: Vec<u8> = BinaryTrace::new(Vec::new()).unwrap().finish().unwrap();
+
+        assert!(read_binary(&bytes[..]).unwrap().is_empty());
+
+        bytes[4] = VERSION + 1;
+        assert_eq!(read_binary(&bytes[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_binary(&b"C8T"[..]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read_binary(&b"NOPE\x01"[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+
+    #[test]
+    fn trapped_instructions_are_not_traced() {
+        let entries: Entries = Entries::default();
+        let mut machine: Machine = Machine::new();
+
+        machine.init_from_bytes(&asm::assemble(": main\n  v0 := 1\n  return\n").unwrap().rom).unwrap();
+        machine.set_fault_policy(FaultPolicy::Trap);
+        machine.set_tracer(Some(Box::new(entries.clone())));
+
+        machine.step_instruction().unwrap();
+
+        // the return underflows the stack every time it is retried
+        for _ in 0..2 {
+            assert!(machine.step_instruction().is_err());
+        }
+
+        let pcs: Vec<u16> = entries.0.borrow().iter().map(|entry| entry.pc).collect();
+
+        assert_eq!(pcs, [0x200]);
+    }
+}
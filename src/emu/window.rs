@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use piston_window as pw;
 use pw::{
     PistonWindow,
@@ -5,18 +6,27 @@ use pw::{
     Transformed,
     PressEvent,
     ReleaseEvent,
+    RenderEvent,
     Button
 };
 use super::display;
 
 
+/// A key or button going down or up.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Input {
+    Press(Button),
+    Release(Button)
+}
+
+
 #[allow(dead_code)]
 pub struct Window {
     window: PistonWindow,
-    x_scale: f64,
-    y_scale: f64,
-    pressed_button: Button,
-    released_button: Button
+    width: f64,
+    height: f64,
+    palette: display::Palette,
+    inputs: VecDeque<Input>     // received since the machine last read them, oldest first
 }
 
 
@@ -30,50 +40,60 @@ impl Window {
 
         Self {
             window,
-            x_scale: width as f64 / 64.0,
-            y_scale: height as f64 / 32.0,
-            pressed_button: Button::Keyboard(pw::Key::Unknown),
-            released_button: Button::Keyboard(pw::Key::Unknown)
+            width: width as f64,
+            height: height as f64,
+            palette: display::DEFAULT_PALETTE,
+            inputs: VecDeque::new()
         }
     }
 
-    pub fn update_screen(&mut self, display: &mut display::Display) {
-        if let Some(e) = self.window.next() {
+    pub fn set_palette(&mut self, palette: display::Palette) {
+        self.palette = palette;
+    }
+
+    /// Queues the input events that arrived since the last call and draws
+    /// the display on the next render event.
+    pub fn update_screen(&mut self, display: &display::Display) {
+        while let Some(e) = self.window.next() {
             if let Some(button) = e.press_args() {
-                self.pressed_button = button;
+                self.inputs.push_back(Input::Press(button));
             }
 
             if let Some(button) = e.release_args() {
-                self.released_button = button;
-            } 
+                self.inputs.push_back(Input::Release(button));
+            }
+
+            if e.render_args().is_none() {
+                continue;
+            }
+
+            // framebuffer resolution changes when SUPER-CHIP switches between lores and hires
+            let columns: usize = display.width();
+            let x_scale: f64 = self.width / columns as f64;
+            let y_scale: f64 = self.height / display.height() as f64;
 
             self.window.draw_2d(&e, |c, g, _| {
-                pw::clear([0.0, 0.5, 0.0, 1.0], g);
+                pw::clear(self.palette[0], g);
 
                 for i in 0..display.get_size() {
-                    if display.read_pixel(i) {
-                        let x: f64 = (i as u32 % 64) as f64 * self.x_scale;
-                        let y: f64 = (i as u32 / 64) as f64 * self.y_scale;
+                    let color: usize = display.read_pixel(i) as usize;
+
+                    if color != 0 {
+                        let x: f64 = (i % columns) as f64 * x_scale;
+                        let y: f64 = (i / columns) as f64 * y_scale;
                         let c = c.trans(x, y);
-                        let rect = [self.x_scale, self.x_scale, self.y_scale, self.y_scale];
-                        pw::rectangle([0.0, 0.0, 0.0, 1.0], rect, c.transform, g);
+                        let rect = [0.0, 0.0, x_scale, y_scale];
+                        pw::rectangle(self.palette[color], rect, c.transform, g);
                     }
                 }
             });
-        }
-    }
 
-    pub fn get_presssed_key(&mut self) -> Button {
-        let key = self.pressed_button;
-        
-        self.pressed_button = Button::Keyboard(pw::Key::Unknown);
-        key
+            return;
+        }
     }
 
-    pub fn get_released_key(&mut self) -> Button {
-        let key = self.released_button;
-
-        self.released_button = Button::Keyboard(pw::Key::Unknown);
-        key
+    /// Oldest input event that has not been handled yet.
+    pub fn next_input(&mut self) -> Option<Input> {
+        self.inputs.pop_front()
     }
 }
@@ -0,0 +1,35 @@
+//! CHIP-8 emulator core.
+//!
+//! The core is headless: a [`Machine`] can be created, loaded and stepped
+//! frame by frame without opening any window. The piston frontend is only
+//! compiled with the `gui` feature.
+
+mod emu;
+
+pub use emu::{
+    Machine,
+    State,
+    asm,
+    audio,
+    bus,
+    config,
+    cpu,
+    debugger,
+    disasm,
+    ram,
+    display,
+    error,
+    harness,
+    input,
+    keymap,
+    movie,
+    platform,
+    rewind,
+    savestate,
+    trace
+};
+
+pub use emu::error::Chip8Error;
+
+#[cfg(feature = "gui")]
+pub use emu::window;
@@ -1,21 +1,338 @@
+use std::{
+    env,
+    fs,
+    io,
+    path::PathBuf,
+    process
+};
 use native_dialog::FileDialog;
+use chip_8_emu::{
+    Chip8Error,
+    Machine,
+    State,
+    config::{
+        Config,
+        Settings
+    },
+    cpu::quirks::Quirks,
+    display,
+    harness,
+    movie::{
+        self,
+        Movie
+    },
+    rewind::Rewind,
+    trace
+};
 
-mod emu;
+const USAGE: &str = "usage: chip-8-emu [options] [rom]
+
+Opens a file dialog when no rom is given. Flags override the config file.
+
+options:
+  -c, --config <file>       config file instead of ~/.config/chip-8-emu/config.toml
+      --no-config           ignore the config file
+      --hash                print the hash identifying the rom in the config file
+  -p, --platform <name>     vip, chip48, schip or xochip (default vip)
+  -q, --quirk <name=value>  override one quirk of the platform, e.g. wrap=1
+  -i, --ipf <n>             instructions per 60hz frame (default 8)
+  -s, --scale <n>           window size in host pixels per lores pixel
+      --palette <colours>   2 or 4 hex colours, e.g. 000000,ffffff
+      --layout <name>       qwerty, azerty, dvorak or numpad
+  -k, --keymap <file>       host key bindings, one hex key and its host keys per line
+  -b, --bind <key=host>     also press a hex key with a host key, e.g. 2=Up
+      --pad-bind <key=button>
+                            also press a hex key with a controller button, e.g. 5=South,
+                            or by driver code for unnamed buttons, e.g. 5=Button704
+      --seed <n>            seed of the CXNN random generator
+      --rng <name>          xorshift or vip
+      --headless            run without a window
+  -n, --frames <n>          stop after n frames
+      --screenshot <file>   write the final display as .png or ASCII art
+      --ascii               print the final display to stdout
+      --record <movie>      record the keypad into a movie
+      --replay <movie>      replay and verify a movie, implies --headless
+      --trace <file>        trace every instruction, binary when the file ends in .bin
+      --trace-format <template>
+                            layout of text trace lines, e.g. \"{pc} {op} {regs}\"";
+
+
+struct Options {
+    rom: Option<PathBuf>,
+    settings: Settings,         // the top layer over the config file
+    config: Option<PathBuf>,
+    no_config: bool,
+    hash: bool,
+    headless: bool,
+    frames: Option<u64>,
+    screenshot: Option<PathBuf>,
+    ascii: bool,
+    record: Option<String>,
+    replay: Option<String>,
+    trace: Option<String>,
+    trace_format: trace::TraceFormat
+}
+
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("chip-8-emu: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+
+fn fail(message: String) -> ! {
+    eprintln!("chip-8-emu: {}", message);
+    process::exit(1);
+}
+
+
+// decimal or 0x prefixed hex
+fn parse_number<T: TryFrom<u64>>(text: &str) -> Result<T, String> {
+    let value: Option<u64> = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
+    };
+
+    value
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or(format!("bad number '{}'", text))
+}
+
+
+// hex key and the name of a key or button, e.g. 2=Up
+fn parse_binding(binding: &str) -> Result<(u8, String), String> {
+    binding
+        .split_once('=')
+        .and_then(|(key, name)| Some((u8::from_str_radix(key, 16).ok().filter(|key| *key < 16)?, name.to_string())))
+        .ok_or(format!("binding '{}' must be a hex key and a name, e.g. 2=Up", binding))
+}
+
+
+fn parse_args() -> Result<Options, String> {
+    let mut options: Options = Options {
+        rom: None,
+        settings: Settings::default(),
+        config: None,
+        no_config: false,
+        hash: false,
+        headless: false,
+        frames: None,
+        screenshot: None,
+        ascii: false,
+        record: None,
+        replay: None,
+        trace: None,
+        trace_format: trace::TraceFormat::default()
+    };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+
+        let settings: &mut Settings = &mut options.settings;
+
+        match arg.as_str() {
+            "-c" | "--config" => options.config = Some(value()?.into()),
+            "--no-config" => options.no_config = true,
+            "--hash" => options.hash = true,
+            "-p" | "--platform" => settings.platform = Some(value()?.parse()?),
+            "-q" | "--quirk" => {
+                let quirk: String = value()?;
+                let (name, value) = quirk.split_once('=').ok_or(format!("quirk '{}' must be name=value", quirk))?;
+
+                // names and values do not depend on the platform, a typo is a usage error
+                Quirks::default().set(name, value)?;
+                settings.quirks.push((name.to_string(), value.to_string()));
+            }
+            "-i" | "--ipf" => settings.instructions_per_frame = Some(parse_number(&value()?)?),
+            "-s" | "--scale" => {
+                let scale: u32 = parse_number(&value()?)?;
+
+                settings.window_size = Some((display::LORES_WIDTH as u32 * scale, display::LORES_HEIGHT as u32 * scale));
+            }
+            "--palette" => settings.palette = Some(display::parse_palette(&value()?)?),
+            "--layout" => settings.layout = Some(value()?.parse()?),
+            "-k" | "--keymap" => settings.keymap = Some(value()?.into()),
+            "-b" | "--bind" => settings.keys.push(parse_binding(&value()?)?),
+            "--pad-bind" => settings.gamepad.push(parse_binding(&value()?)?),
+            "--seed" => settings.seed = Some(parse_number(&value()?)?),
+            "--rng" => settings.rng = Some(value()?.parse()?),
+            "--headless" => options.headless = true,
+            "-n" | "--frames" => options.frames = Some(parse_number(&value()?)?),
+            "--screenshot" => options.screenshot = Some(value()?.into()),
+            "--ascii" => options.ascii = true,
+            "--record" => options.record = Some(value()?),
+            "--replay" => options.replay = Some(value()?),
+            "--trace" => options.trace = Some(value()?),
+            "--trace-format" => options.trace_format = value()?.parse()?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if options.rom.is_none() && !arg.starts_with('-') => options.rom = Some(arg.into()),
+            _ => return Err(format!("unexpected argument '{}'", arg))
+        }
+    }
+
+    Ok(options)
+}
+
+
+// global and per-rom settings of the config file, empty when there is none
+fn load_config(options: &Options) -> Result<Config, String> {
+    let path: PathBuf = match (options.no_config, options.config.clone()) {
+        (true, _) => return Ok(Config::default()),
+        (false, Some(path)) => path,
+        (false, None) => match Config::default_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default())
+        }
+    };
+
+    Config::load(&path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+
+fn build_machine(settings: &Settings, options: &Options) -> Result<Machine, String> {
+    let mut machine: Machine = settings.machine()?;
+
+    if let Some(path) = options.trace.as_ref() {
+        let tracer: Box<dyn trace::TraceSink> = if path.ends_with(".bin") {
+            Box::new(trace::BinaryTrace::create(path).map_err(|err| format!("{}: {}", path, err))?)
+        } else {
+            let trace: trace::TextTrace<io::BufWriter<fs::File>> = trace::TextTrace::create(path)
+                .map_err(|err| format!("{}: {}", path, err))?;
+
+            Box::new(trace.with_format(options.trace_format.clone()))
+        };
+
+        machine.set_tracer(Some(tracer));
+    }
+
+    machine.set_frame_limit(options.frames);
+
+    Ok(machine)
+}
+
+
+// runs until the rom exits or the frame limit is reached
+fn run_headless(machine: &mut Machine, frames: Option<u64>) -> Result<(), Chip8Error> {
+    while machine.state() == State::Running && frames.is_none_or(|limit| machine.frame() < limit) {
+        machine.step_frame()?;
+    }
+
+    Ok(())
+}
+
+
+fn write_outputs(machine: &Machine, options: &Options) -> Result<(), String> {
+    if let Some(path) = options.screenshot.as_ref() {
+        harness::write_display(machine.display(), path).map_err(|err| err.to_string())?;
+    }
+
+    if options.ascii {
+        print!("{}", harness::to_ascii(machine.display()));
+    }
+
+    Ok(())
+}
 
 
 fn main() {
-    let result = FileDialog::new()
-        .set_location("~")
-        .add_filter("CHIP-8 ROMS", &["ch8"])
-        .show_open_single_file()
-        .unwrap()
-        .unwrap();
+    let options: Options = parse_args().unwrap_or_else(|message| usage_error(&message));
+    let headless: bool = options.headless || options.replay.is_some() || options.hash;
+
+    let rom_path: PathBuf = match options.rom.clone() {
+        Some(path) => path,
+        None if headless => usage_error("no rom given"),
+        None => match FileDialog::new()
+            .set_location("~")
+            .add_filter("CHIP-8 ROMS", &["ch8"])
+            .show_open_single_file()
+        {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(err) => fail(format!("cannot open the file dialog: {}", err))
+        }
+    };
+
+    let rom_name: String = rom_path.to_string_lossy().into_owned();
+    let rom: Vec<u8> = fs::read(&rom_path).unwrap_or_else(|err: io::Error| fail(format!("{}: {}", rom_name, err)));
+
+    if options.hash {
+        println!("{:016x}", movie::hash(&rom));
+        return;
+    }
+
+    if let Some(path) = options.replay.as_ref() {
+        let movie: Movie = Movie::load(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+
+        match movie.replay(&rom) {
+            Ok(machine) => {
+                eprintln!("replay of {} frames verified", movie.frames.len());
+                write_outputs(&machine, &options).unwrap_or_else(|message| fail(message));
+            }
+            Err(err) => fail(format!("replay failed: {}", err))
+        }
+
+        return;
+    }
+
+    let mut settings: Settings = load_config(&options)
+        .unwrap_or_else(|message| fail(message))
+        .settings(movie::hash(&rom));
+
+    settings.merge(&options.settings);
+
+    let mut machine: Machine = build_machine(&settings, &options).unwrap_or_else(|message| fail(message));
+
+    if !headless {
+        machine.set_rewind(Some(Rewind::default()));
+    }
+
+    #[cfg(feature = "sound")]
+    if !headless {
+        match chip_8_emu::audio::DeviceSink::new(chip_8_emu::audio::Tone::default()) {
+            Ok(sink) => machine.set_audio_sink(Box::new(sink)),
+            Err(err) => eprintln!("sound disabled: {}", err)
+        }
+    }
+
+    #[cfg(feature = "gamepad")]
+    if !headless {
+        match chip_8_emu::input::Gamepads::new() {
+            Ok(gamepads) => machine.add_input_source(Box::new(gamepads)),
+            Err(err) => eprintln!("controllers disabled: {}", err)
+        }
+    }
+
+    if let Err(err) = machine.init(rom_name) {
+        fail(format!("failed to load rom: {}", err));
+    }
+
+    if options.record.is_some() {
+        machine.start_recording();
+    }
+
+    let result: Result<(), Chip8Error> = if headless {
+        run_headless(&mut machine, options.frames)
+    } else {
+        machine.run();
+        Ok(())
+    };
+
+    // tracers flush when they are dropped
+    machine.set_tracer(None);
 
-    let rom_path: String = String::from(result.to_str().unwrap());
+    if let (Some(path), Some(movie)) = (options.record.as_ref(), machine.stop_recording()) {
+        if let Err(err) = movie.save(path) {
+            eprintln!("failed to save movie: {}", err);
+        }
+    }
 
-    let mut emulator: emu::Machine = emu::Machine::new();
+    write_outputs(&machine, &options).unwrap_or_else(|message| fail(message));
 
-    emulator
-        .init(rom_path)
-        .run();
+    if let Err(err) = result {
+        fail(format!("emulation stopped: {}", err));
+    }
 }
@@ -0,0 +1,121 @@
+use chip_8_emu::{
+    asm::{
+        self,
+        AsmError
+    },
+    cpu::instruction::Syntax,
+    disasm::{
+        Entry,
+        Listing
+    }
+};
+
+const PROGRAM: &str = "
+: main
+  v1 := 0
+  i := sprite
+  loop
+    if v1 == 3 then v2 += 1
+    if v1 != 8 begin
+      v3 := random 0x0F
+    else
+      v3 := key
+    end
+    sprite v1 v2 1
+    draw
+    v1 += 1
+    if v1 != 16 then
+  again
+  jump main
+
+: draw
+  vf := 1
+  i := long sprite
+  return
+
+: sprite
+  0x80 0xC0 0xE0
+";
+
+
+fn error(source: &str) -> AsmError {
+    match asm::assemble(source) {
+        Ok(_) => panic!("assembled without an error"),
+        Err(err) => err
+    }
+}
+
+
+#[test]
+fn round_trip_through_the_disassembler() {
+    let rom: Vec<u8> = asm::assemble(PROGRAM).unwrap().rom;
+
+    // without labels every operand is an absolute address
+    let source: String = Listing::trace(&rom)
+        .entries()
+        .iter()
+        .map(|entry| match entry {
+            Entry::Code { op, .. } => op.disassemble(Syntax::Octo).to_string(),
+            Entry::Data { bytes, .. } => bytes.iter().map(|byte| format!("0x{:02X} ", byte)).collect()
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    assert_eq!(asm::assemble(&source).unwrap().rom, rom);
+}
+
+
+#[test]
+fn forward_references_are_fixed_up() {
+    let assembly: asm::Assembly = asm::assemble("
+        : main
+          :call later
+          i := long data
+          jump main
+        : later
+          return
+        : data
+          0xAB
+    ").unwrap();
+
+    assert_eq!(assembly.rom, [0x22, 0x08, 0xF0, 0x00, 0x02, 0x0A, 0x12, 0x00, 0x00, 0xEE, 0xAB][..]);
+    assert_eq!(assembly.symbols.get("later"), Some(0x208));
+    assert_eq!(assembly.symbols.get("data"), Some(0x20A));
+}
+
+
+#[test]
+fn undefined_label() {
+    let err: AsmError = error(": main\n  jump nowhere\n");
+
+    assert_eq!((err.line, err.column), (2, 8));
+    assert!(err.message.contains("undefined label 'nowhere'"), "{}", err);
+}
+
+
+#[test]
+fn jump_out_of_range() {
+    let err: AsmError = error(": main\n  jump far\n:org 0x1000\n: far\n  return\n");
+
+    assert_eq!(err.line, 2);
+    assert!(err.message.contains("out of 12 bit range"), "{}", err);
+
+    let err: AsmError = error(": main\n  jump 0x1000\n");
+
+    assert_eq!(err.line, 2);
+    assert!(err.message.contains("out of 12 bit range"), "{}", err);
+
+    // a loop jumps back to its start
+    let err: AsmError = error(": main\n  return\n:org 0x1000\n  loop\n  again\n");
+
+    assert_eq!(err.line, 5);
+    assert!(err.message.contains("outside of 12 bit range"), "{}", err);
+}
+
+
+#[test]
+fn recursive_macro() {
+    let err: AsmError = error(":macro forever { forever }\n: main\n  forever\n");
+
+    assert!(err.message.contains("recursive"), "{}", err);
+}
@@ -0,0 +1,75 @@
+use chip_8_emu::{
+    Machine,
+    asm
+};
+
+// plays the pattern at the default pitch for two frames, then an octave higher
+const PROGRAM: &str = "
+: main
+  i := pattern
+  audio
+  v0 := 30
+  buzzer := v0
+  v1 := 2
+  delay := v1
+: wait
+  v1 := delay
+  if v1 != 0 then jump wait
+  v2 := 112
+  pitch := v2
+: spin
+  jump spin
+
+: pattern
+  0xF0 0x0F 0xAA 0xCC 0x81 0x42 0x24 0x18
+  0x00 0xFF 0x00 0xFF 0x00 0xFF 0x00 0xFF
+";
+
+const PATTERN: [u8; 16] = [
+    0xF0, 0x0F, 0xAA, 0xCC, 0x81, 0x42, 0x24, 0x18,
+    0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF
+];
+
+// the default pitch plays 4000 bits per second, one bit per sample at this rate
+const SAMPLE_RATE: u32 = 4000;
+
+
+fn sample(bit: usize) -> f32 {
+    if PATTERN[bit / 8] & (0x80 >> (bit % 8)) != 0 { 1.0 } else { -1.0 }
+}
+
+
+fn render(machine: &mut Machine, samples: usize) -> Vec<f32> {
+    let mut out: Vec<f32> = vec![0.5; samples];
+
+    machine.render_audio(&mut out, SAMPLE_RATE);
+    out
+}
+
+
+#[test]
+fn pattern_plays_at_the_pitch_until_the_sound_timer_runs_out() {
+    let mut machine: Machine = Machine::new();
+
+    machine.init_from_bytes(&asm::assemble(PROGRAM).unwrap().rom).unwrap();
+    machine.step_frame().unwrap();
+
+    assert_eq!(render(&mut machine, 16), (0..16).map(sample).collect::<Vec<f32>>());
+
+    // FX3A with 112 doubles the rate, every other bit is played from where the last frame stopped
+    for _ in 0..3 {
+        machine.step_frame().unwrap();
+    }
+
+    assert_eq!(render(&mut machine, 8), (16..32).step_by(2).map(sample).collect::<Vec<f32>>());
+
+    // the pattern loops back to its first bit
+    assert_eq!(render(&mut machine, 64), (32..128).chain(0..32).step_by(2).map(sample).collect::<Vec<f32>>());
+
+    for _ in 0..30 {
+        machine.step_frame().unwrap();
+    }
+
+    assert_eq!(machine.cpu().sound_timer(), 0);
+    assert_eq!(render(&mut machine, 16), [0.0; 16]);
+}
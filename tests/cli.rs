@@ -0,0 +1,74 @@
+//! Runs the chip-8-emu binary, which needs the gui feature.
+#![cfg(feature = "gui")]
+
+mod common;
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{
+        Command,
+        Output
+    }
+};
+use chip_8_emu::movie;
+use common::assemble_fixture;
+
+
+// written once per test, the tests run in parallel
+fn font_rom(test: &str) -> (PathBuf, Vec<u8>) {
+    let rom: Vec<u8> = assemble_fixture("font.8o");
+    let path: PathBuf = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.ch8", test));
+
+    fs::write(&path, &rom).unwrap();
+    (path, rom)
+}
+
+
+fn run(test: &str, args: &[&str]) -> Output {
+    let (path, _) = font_rom(test);
+
+    Command::new(env!("CARGO_BIN_EXE_chip-8-emu"))
+        .arg("--no-config")
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap()
+}
+
+
+#[test]
+fn headless_run_prints_the_display() {
+    let output: Output = run("headless", &["--headless", "-n", "20", "--ascii"]);
+    let golden: String = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/font.txt")).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), golden.trim_end());
+}
+
+
+#[test]
+fn hash_identifies_the_rom() {
+    let (_, rom) = font_rom("hash");
+    let output: Output = run("hash", &["--hash"]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{:016x}\n", movie::hash(&rom)));
+}
+
+
+#[test]
+fn bad_flags_are_usage_errors() {
+    for args in [
+        ["--quirk", "wrap=2"],
+        ["--quirk", "warp=1"],
+        ["--quirk", "stack-depth=0"],
+        ["--pad-bind", "5"],
+        ["--pad-bind", "g=South"]
+    ] {
+        let output: Output = run("usage", &[&["--headless"], &args[..]].concat());
+
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("chip-8-emu: "), "{:?}", args);
+    }
+}
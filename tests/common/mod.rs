@@ -0,0 +1,16 @@
+//! Fixtures shared by the integration tests.
+
+use std::fs;
+use chip_8_emu::asm;
+
+
+/// Assembles `tests/roms/<name>`, e.g. `font.8o`.
+pub fn assemble_fixture(name: &str) -> Vec<u8> {
+    let path: String = format!("{}/tests/roms/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let source: String = fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path, err));
+
+    match asm::assemble(&source) {
+        Ok(assembly) => assembly.rom,
+        Err(err) => panic!("{}:{}:{}: {}", name, err.line, err.column, err.message)
+    }
+}
@@ -0,0 +1,82 @@
+//! Community test roms, the Timendus CHIP-8 test suite.
+//!
+//! The suite is GPL-3.0 licensed, so its roms are not vendored. The tests
+//! read them from the directory in `CHIP8_TEST_SUITE`, which CI fills by
+//! cloning the suite, and pass with a note when it is not set. To run them
+//! locally:
+//!
+//! ```sh
+//! git clone https://github.com/Timendus/chip8-test-suite /tmp/chip8-test-suite
+//! CHIP8_TEST_SUITE=/tmp/chip8-test-suite/bin cargo test --test community
+//! ```
+//!
+//! The goldens in `tests/golden/community` must show what a reference
+//! emulator such as Octo shows after the same number of frames, compare by
+//! eye before committing one. A missing rom in CI or a missing golden fails
+//! the test.
+
+use std::{
+    env,
+    fs,
+    path::PathBuf
+};
+use chip_8_emu::{
+    harness::Harness,
+    platform::Platform
+};
+
+
+fn run(rom: &str, platform: Platform, frames: u64, keys: &[(u64, usize)]) {
+    let Some(suite) = env::var_os("CHIP8_TEST_SUITE") else {
+        assert!(env::var_os("CI").is_none(), "CHIP8_TEST_SUITE is not set, CI must fetch the test suite");
+        eprintln!("skipping {}, CHIP8_TEST_SUITE is not set, see tests/community.rs", rom);
+        return;
+    };
+
+    let dir: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests"].iter().collect();
+    let path: PathBuf = PathBuf::from(suite).join(rom);
+    let bytes: Vec<u8> = fs::read(&path)
+        .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    let mut harness: Harness = Harness::with_platform(platform, &bytes).unwrap();
+
+    for (frame, key) in keys {
+        harness.tap(*frame, *key, 5);
+    }
+
+    harness.run(frames).unwrap();
+
+    if let Err(err) = harness.check_golden(dir.join("golden/community").join(rom).with_extension("txt")) {
+        panic!("{}", err);
+    }
+}
+
+
+#[test]
+fn chip8_logo() {
+    run("1-chip8-logo.ch8", Platform::CosmacVip, 60, &[]);
+}
+
+
+#[test]
+fn ibm_logo() {
+    run("2-ibm-logo.ch8", Platform::CosmacVip, 60, &[]);
+}
+
+
+#[test]
+fn corax_plus() {
+    run("3-corax+.ch8", Platform::CosmacVip, 60, &[]);
+}
+
+
+#[test]
+fn flags() {
+    run("4-flags.ch8", Platform::CosmacVip, 120, &[]);
+}
+
+
+#[test]
+fn quirks() {
+    // key 1 picks the CHIP-8 tests from the menu
+    run("5-quirks.ch8", Platform::CosmacVip, 600, &[(30, 0x1)]);
+}
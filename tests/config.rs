@@ -0,0 +1,108 @@
+use std::path::Path;
+use chip_8_emu::{
+    config::{
+        Config,
+        Settings
+    },
+    cpu::quirks::MemoryIncrement,
+    keymap::KeyMap,
+    platform::Platform
+};
+
+const CONFIG: &str = r#"
+platform = "vip"
+instructions-per-frame = 10
+window = [1280, 640]
+keymap = "keys.txt"
+
+[quirks]
+display-wait = false
+
+[rom.00000000000000ff]
+name = "Some game"
+platform = "schip"
+scale = 8
+quirks.memory-increment = "x"
+"#;
+
+
+#[test]
+fn rom_sections_override_global_settings() {
+    let config: Config = Config::parse(CONFIG, Path::new("/etc/chip8")).unwrap();
+    let global: Settings = config.settings(0x1234);
+    let rom: Settings = config.settings(0xFF);
+
+    assert_eq!(global.platform, Some(Platform::CosmacVip));
+    assert_eq!(global.window_size, Some((1280, 640)));
+    assert_eq!(global.keymap.as_deref(), Some(Path::new("/etc/chip8/keys.txt")));
+
+    assert_eq!(rom.platform, Some(Platform::SuperChip));
+    assert_eq!(rom.instructions_per_frame, Some(10));
+    assert_eq!(rom.window_size, Some((512, 256)));
+
+    let quirks = rom.quirks().unwrap();
+
+    assert!(!quirks.display_wait);
+    assert_eq!(quirks.memory_increment, MemoryIncrement::ByX);
+}
+
+
+#[test]
+fn command_line_overrides_config() {
+    let mut settings: Settings = Config::parse(CONFIG, Path::new("")).unwrap().settings(0xFF);
+    let flags: Settings = Settings {
+        instructions_per_frame: Some(20),
+        quirks: vec![(String::from("display-wait"), String::from("1"))],
+        ..Settings::default()
+    };
+
+    settings.merge(&flags);
+
+    assert_eq!(settings.instructions_per_frame, Some(20));
+    assert_eq!(settings.platform, Some(Platform::SuperChip));
+    assert!(settings.quirks().unwrap().display_wait);
+}
+
+
+#[test]
+fn errors_name_the_line() {
+    let error = |text: &str| Config::parse(text, Path::new("")).unwrap_err().to_string();
+
+    assert!(error("platform = \"vip\"\n\nspeed = 3\n").starts_with("invalid config, line 3: unknown field `speed`"));
+    assert_eq!(
+        error("seed = 1\n[rom.ff]\nplatform = \"chip9\"\n"),
+        "invalid config, line 3: unknown platform 'chip9', expected vip, chip48, schip or xochip"
+    );
+    assert_eq!(
+        error("[rom.ff.keys]\n2 = \"Up\"\n10 = \"Down\"\n"),
+        "invalid config, line 3: '10' is not a keypad key"
+    );
+    assert!(error("[rom.game]\nseed = 1\n").contains("'game' is not a rom hash"));
+}
+
+
+#[test]
+fn quirks_are_checked_by_the_settings() {
+    let config: Config = Config::parse("[rom.ff.quirks]\nstack-depth = 0\n", Path::new("")).unwrap();
+
+    assert!(config.settings(0x1).quirks().is_ok());
+    assert_eq!(config.settings(0xFF).quirks().unwrap_err(), "stack depth must be 1 to 255, not '0'");
+}
+
+
+#[test]
+fn per_rom_key_bindings() {
+    let config: Config = Config::parse(
+        "layout = \"azerty\"\n[rom.ff.keys]\n2 = \"Up\"\n8 = [\"Down\", \"NumPad2\"]\n",
+        Path::new("")
+    ).unwrap();
+    let global: KeyMap = config.settings(0x1).keymap().unwrap();
+    let rom: KeyMap = config.settings(0xFF).keymap().unwrap();
+
+    assert_eq!(global.key("Z"), Some(0x5));
+    assert_eq!(global.key("Up"), None);
+
+    assert_eq!(rom.key("up"), Some(0x2));
+    assert_eq!(rom.key("NumPad2"), Some(0x8));
+    assert_eq!(rom.hosts(0x8), vec!["down", "numpad2", "s"]);
+}
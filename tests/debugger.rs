@@ -0,0 +1,173 @@
+use chip_8_emu::{
+    Machine,
+    asm::{
+        self,
+        SymbolMap
+    },
+    debugger::{
+        Stop,
+        repl,
+        watch::{
+            Access,
+            AccessKind,
+            Location,
+            Watch,
+            WatchKind
+        }
+    }
+};
+
+const PROGRAM: &str = "
+: main
+  :call outer
+  v0 := 1
+  i := buffer
+  save v1
+: spin
+  jump spin
+
+: outer
+  v1 := 5
+  :call inner
+  return
+
+: inner
+  v2 := 7
+  return
+
+: buffer
+  0x00 0x00
+";
+
+const OUTER: u16 = 0x20A;
+const INNER: u16 = 0x210;
+const BUFFER: usize = 0x214;
+
+
+fn machine() -> Machine {
+    let mut machine: Machine = Machine::new();
+
+    machine.init_from_bytes(&asm::assemble(PROGRAM).unwrap().rom).unwrap();
+    machine
+}
+
+
+#[test]
+fn step_over_runs_a_call_to_its_return() {
+    let mut machine: Machine = machine();
+
+    assert_eq!(machine.step_over().unwrap(), Stop::Step);
+    assert_eq!(machine.cpu().pc(), 0x202);
+    assert!(machine.cpu().stack().is_empty());
+    assert_eq!(machine.cpu().v()[1..3], [5, 7]);
+}
+
+
+#[test]
+fn step_out_returns_one_level() {
+    let mut machine: Machine = machine();
+
+    machine.step().unwrap();
+    assert_eq!(machine.cpu().pc(), OUTER);
+    machine.step().unwrap();
+    machine.step().unwrap();
+    assert_eq!(machine.cpu().pc(), INNER);
+
+    assert_eq!(machine.step_out().unwrap(), Stop::Step);
+    assert_eq!(machine.cpu().pc(), OUTER + 4);
+    assert_eq!(machine.cpu().stack().len(), 1);
+    assert_eq!(machine.cpu().v()[2], 7);
+
+    assert_eq!(machine.step_out().unwrap(), Stop::Step);
+    assert_eq!(machine.cpu().pc(), 0x202);
+    assert!(machine.cpu().stack().is_empty());
+}
+
+
+#[test]
+fn memory_watchpoint_fires_on_save() {
+    let mut machine: Machine = machine();
+
+    machine.debugger_mut().add_watch(Watch::memory(BUFFER + 1, WatchKind::Write));
+
+    let access: Access = Access {
+        location: Location::Memory(BUFFER + 1),
+        kind: AccessKind::Write,
+        value: 5
+    };
+
+    assert_eq!(machine.resume(60).unwrap(), Stop::Watchpoint { addr: 0x206, access });
+    assert_eq!(machine.ram().space()[BUFFER..BUFFER + 2], [1, 5]);
+}
+
+
+#[test]
+fn resume_stops_after_the_frame_limit() {
+    let mut machine: Machine = machine();
+
+    assert_eq!(machine.resume(3).unwrap(), Stop::Frame { frame: 3 });
+    assert_eq!(machine.frame(), 3);
+    assert_eq!(machine.cpu().pc(), 0x208);
+}
+
+
+#[test]
+fn register_watchpoint_fires_on_a_write() {
+    let mut machine: Machine = machine();
+
+    machine.debugger_mut().add_watch(Watch::Register { x: 2 });
+
+    let access: Access = Access {
+        location: Location::V(2),
+        kind: AccessKind::Write,
+        value: 7
+    };
+
+    assert_eq!(machine.resume(60).unwrap(), Stop::Watchpoint { addr: INNER, access });
+    assert_eq!(machine.cpu().pc(), INNER + 2);
+}
+
+
+#[test]
+fn index_watchpoint_fires_on_a_write() {
+    let mut machine: Machine = machine();
+
+    machine.debugger_mut().add_watch(Watch::Index);
+
+    let access: Access = Access {
+        location: Location::I,
+        kind: AccessKind::Write,
+        value: BUFFER as u16
+    };
+
+    assert_eq!(machine.resume(60).unwrap(), Stop::Watchpoint { addr: 0x204, access });
+    assert_eq!(machine.cpu().i(), BUFFER as u16);
+}
+
+
+#[test]
+fn step_over_stops_at_a_breakpoint_inside_the_call() {
+    let mut machine: Machine = machine();
+
+    machine.debugger_mut().add_breakpoint(INNER);
+
+    assert_eq!(machine.step_over().unwrap(), Stop::Breakpoint { addr: INNER });
+    assert_eq!(machine.cpu().stack().len(), 2);
+
+    // stepping out of both calls lands after the first one
+    assert_eq!(machine.step_out().unwrap(), Stop::Step);
+    assert_eq!(machine.step_out().unwrap(), Stop::Step);
+    assert_eq!(machine.cpu().pc(), 0x202);
+}
+
+
+#[test]
+fn repl_only_presses_hex_keys() {
+    let mut machine: Machine = machine();
+    let mut output: Vec<u8> = Vec::new();
+
+    repl::run(&mut machine, &SymbolMap::default(), &b"key 10 down\nkey a down\n"[..], &mut output).unwrap();
+
+    assert!(String::from_utf8(output).unwrap().contains("bad key '10'"));
+    assert_eq!(machine.keypad().iter().position(|pressed| *pressed), Some(0xA));
+}
@@ -0,0 +1,35 @@
+use chip_8_emu::disasm::{
+    Entry,
+    Listing
+};
+
+
+fn addr(entry: &Entry) -> u16 {
+    match entry {
+        Entry::Code { addr, .. } | Entry::Data { addr, .. } => *addr
+    }
+}
+
+
+#[test]
+fn rom_is_cut_at_the_end_of_the_address_space() {
+    // jumps to the last word, everything else is data
+    let mut rom: Vec<u8> = vec![0xAA; 0x10000];
+
+    rom[0..2].copy_from_slice(&[0x1F, 0xFE]);
+
+    let listing: Listing = Listing::trace(&rom);
+    let entries: &[Entry] = listing.entries();
+
+    assert!(entries.windows(2).all(|pair| addr(&pair[0]) < addr(&pair[1])));
+
+    let bytes: usize = entries
+        .iter()
+        .map(|entry| match entry {
+            Entry::Code { bytes, .. } | Entry::Data { bytes, .. } => bytes.len()
+        })
+        .sum();
+
+    assert_eq!(bytes, 0x10000 - 0x200);
+    assert!(matches!(entries.last(), Some(Entry::Code { addr: 0xFFFE, .. })));
+}
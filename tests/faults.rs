@@ -0,0 +1,100 @@
+use chip_8_emu::{
+    Chip8Error,
+    Machine,
+    State,
+    asm,
+    error::FaultPolicy
+};
+
+// calls itself until the 12 entries of the VIP stack are used up
+const OVERFLOW: &str = ": main\n  :call main\n";
+const UNDERFLOW: &str = ": main\n  return\n";
+// the second byte loaded is one past the end of the 4 KiB memory
+const OUT_OF_BOUNDS: &str = ": main\n  i := 0xFFF\n  load v1\n: spin\n  jump spin\n";
+const UNKNOWN: &str = ": main\n  0x80 0x08\n";
+
+
+// runs up to 100 instructions, stopping at the first fault
+fn run(source: &str, policy: FaultPolicy) -> (Machine, Result<(), Chip8Error>) {
+    let mut machine: Machine = Machine::new();
+
+    machine.init_from_bytes(&asm::assemble(source).unwrap().rom).unwrap();
+    machine.set_fault_policy(policy);
+
+    for _ in 0..100 {
+        if let Err(err) = machine.step_instruction() {
+            return (machine, Err(err));
+        }
+    }
+
+    (machine, Ok(()))
+}
+
+
+#[test]
+fn halt_stops_on_every_fault() {
+    let (machine, result) = run(OVERFLOW, FaultPolicy::Halt);
+
+    assert!(matches!(result, Err(Chip8Error::StackOverflow { addr: 0x200 })), "{:?}", result);
+    assert_eq!(machine.cpu().stack().len(), 12);
+    assert_eq!(machine.state(), State::Halted);
+
+    let (machine, result) = run(UNDERFLOW, FaultPolicy::Halt);
+
+    assert!(matches!(result, Err(Chip8Error::StackUnderflow { addr: 0x200 })), "{:?}", result);
+    assert_eq!(machine.state(), State::Halted);
+
+    let (machine, result) = run(OUT_OF_BOUNDS, FaultPolicy::Halt);
+
+    assert!(matches!(result, Err(Chip8Error::OutOfBounds { addr: 0x1000 })), "{:?}", result);
+    assert_eq!(machine.state(), State::Halted);
+
+    let (machine, result) = run(UNKNOWN, FaultPolicy::Halt);
+
+    assert!(matches!(result, Err(Chip8Error::UnknownOpcode { addr: 0x200, opcode: 0x8008 })), "{:?}", result);
+    assert_eq!(machine.state(), State::Halted);
+}
+
+
+#[test]
+fn wrap_folds_the_stack_and_addresses_around() {
+    let (machine, result) = run(OVERFLOW, FaultPolicy::Wrap);
+
+    // 100 calls into a 12 entry stack
+    assert!(result.is_ok());
+    assert_eq!(machine.cpu().stack().len(), 100 % 12);
+
+    // the empty stack pops its last entry, which is still zero
+    let (machine, result) = run(UNDERFLOW, FaultPolicy::Wrap);
+
+    assert!(matches!(result, Err(Chip8Error::UnknownOpcode { .. })), "{:?}", result);
+    assert_eq!(machine.cpu().stack().len(), 11);
+
+    // the font starts at address zero
+    let (machine, result) = run(OUT_OF_BOUNDS, FaultPolicy::Wrap);
+
+    assert!(result.is_ok());
+    assert_eq!(machine.cpu().v()[1], 0xF0);
+
+    // there is nothing to wrap for an unknown opcode
+    let (machine, result) = run(UNKNOWN, FaultPolicy::Wrap);
+
+    assert!(matches!(result, Err(Chip8Error::UnknownOpcode { addr: 0x200, .. })), "{:?}", result);
+    assert_eq!(machine.state(), State::Halted);
+}
+
+
+#[test]
+fn trap_pauses_on_the_faulting_instruction() {
+    for (source, addr) in [(OVERFLOW, 0x200), (UNDERFLOW, 0x200), (OUT_OF_BOUNDS, 0x202), (UNKNOWN, 0x200)] {
+        let (mut machine, result) = run(source, FaultPolicy::Trap);
+
+        assert!(result.is_err());
+        assert_eq!(machine.state(), State::Paused);
+        assert_eq!(machine.cpu().pc(), addr);
+
+        // retrying faults again without moving on
+        assert!(machine.step_instruction().is_err());
+        assert_eq!(machine.cpu().pc(), addr);
+    }
+}
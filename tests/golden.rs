@@ -0,0 +1,95 @@
+//! Runs the roms in tests/roms and compares the display against tests/golden.
+//! Run with CHIP8_BLESS=1 to rewrite the goldens after an intended change.
+
+mod common;
+
+use std::path::PathBuf;
+use chip_8_emu::{
+    harness::Harness,
+    platform::Platform
+};
+use common::assemble_fixture;
+
+
+fn path(dir: &str, name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", dir, name].iter().collect()
+}
+
+
+fn check(harness: &Harness, golden: &str) {
+    if let Err(err) = harness.check_golden(path("golden", golden)) {
+        panic!("{}", err);
+    }
+}
+
+
+#[test]
+fn font() {
+    let mut harness: Harness = Harness::new(&assemble_fixture("font.8o")).unwrap();
+
+    harness.run(20).unwrap();
+    check(&harness, "font.txt");
+}
+
+
+#[test]
+fn scripted_keys() {
+    let mut harness: Harness = Harness::new(&assemble_fixture("keys.8o")).unwrap();
+
+    harness.tap(2, 0xA, 3).tap(12, 0x3, 3);
+
+    harness.run(10).unwrap();
+    check(&harness, "keys-a.txt");
+
+    harness.run(10).unwrap();
+    check(&harness, "keys-3.txt");
+}
+
+
+#[test]
+fn xo_chip_planes() {
+    let mut harness: Harness = Harness::with_platform(Platform::XoChip, &assemble_fixture("planes.8o")).unwrap();
+
+    harness.run(5).unwrap();
+    check(&harness, "planes.txt");
+
+    #[cfg(feature = "png")]
+    check(&harness, "planes.png");
+}
+
+
+#[test]
+fn super_chip_hires_sprites() {
+    let mut harness: Harness = Harness::with_platform(Platform::SuperChip, &assemble_fixture("schip.8o")).unwrap();
+
+    harness.run(5).unwrap();
+    check(&harness, "schip.txt");
+}
+
+
+#[test]
+fn super_chip_scrolling() {
+    let mut harness: Harness = Harness::with_platform(Platform::SuperChip, &assemble_fixture("scroll.8o")).unwrap();
+
+    harness.run(5).unwrap();
+    check(&harness, "scroll.txt");
+}
+
+
+#[test]
+fn lores_clears_the_hires_display() {
+    let mut harness: Harness = Harness::with_platform(Platform::SuperChip, &assemble_fixture("lores.8o")).unwrap();
+
+    harness.run(5).unwrap();
+    assert!(!harness.machine().display().is_hires());
+    check(&harness, "lores.txt");
+}
+
+
+#[test]
+fn flags_keep_registers() {
+    let mut harness: Harness = Harness::with_platform(Platform::SuperChip, &assemble_fixture("flags.8o")).unwrap();
+
+    harness.run(5).unwrap();
+    check(&harness, "flags.txt");
+}
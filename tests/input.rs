@@ -0,0 +1,125 @@
+mod common;
+
+use chip_8_emu::{
+    harness::Harness,
+    input::{
+        PadButton,
+        VirtualController
+    },
+    keymap::{
+        HeldKeys,
+        KeyMap
+    }
+};
+use common::assemble_fixture;
+
+
+#[test]
+fn controller_presses_bound_keys() {
+    let mut harness: Harness = Harness::new(&assemble_fixture("keys.8o")).unwrap();
+    let pad: VirtualController = VirtualController::new();
+
+    harness.machine_mut().add_input_source(Box::new(pad.clone()));
+
+    pad.press(PadButton::DPadLeft);
+    harness.run(3).unwrap();
+    assert!(harness.machine().keypad()[0x4]);
+
+    pad.release(PadButton::DPadLeft);
+    harness.run(3).unwrap();
+    assert!(!harness.machine().keypad()[0x4]);
+
+    // the rom draws the 4 at (28, 13) once the key is released
+    let rows: Vec<String> = harness.ascii().lines().map(|row| row[28..32].to_string()).collect();
+
+    assert_eq!(rows[13..18], ["#..#", "#..#", "####", "...#", "...#"]);
+}
+
+
+#[test]
+fn events_of_one_frame_apply_in_order() {
+    let mut harness: Harness = Harness::new(&assemble_fixture("keys.8o")).unwrap();
+    let pad: VirtualController = VirtualController::new();
+    let mut gamepad_map: KeyMap = KeyMap::gamepad();
+
+    gamepad_map.bind("Button7", 0xC);
+    harness.machine_mut().set_gamepad_map(gamepad_map);
+    harness.machine_mut().add_input_source(Box::new(pad.clone()));
+
+    pad.press(PadButton::South);
+    pad.press(PadButton::Other(7));
+    pad.release(PadButton::South);
+    harness.run(1).unwrap();
+
+    let keypad: &[bool; 16] = harness.machine().keypad();
+
+    assert!(!keypad[0x5]);
+    assert!(keypad[0xC]);
+}
+
+
+#[test]
+fn key_stays_down_while_any_of_its_hosts_is_held() {
+    let mut keymap: KeyMap = KeyMap::new();
+    let mut held: HeldKeys = HeldKeys::new();
+
+    keymap.bind("Up", 0x5);
+
+    assert_eq!(held.press(&keymap, "W"), Some(0x5));
+    assert_eq!(held.press(&keymap, "Up"), Some(0x5));
+    assert_eq!(held.press(&keymap, "up"), Some(0x5));
+
+    assert_eq!(held.release("W"), None);
+    assert!(held.is_pressed(0x5));
+
+    assert_eq!(held.release("UP"), Some(0x5));
+    assert!(!held.is_pressed(0x5));
+    assert_eq!(held.release("Up"), None);
+}
+
+
+#[test]
+fn key_wait_sees_a_press_and_release_in_one_frame() {
+    let mut harness: Harness = Harness::new(&assemble_fixture("keys.8o")).unwrap();
+
+    harness.run(2).unwrap();
+    harness.machine_mut().queue_key("V", true);
+    harness.machine_mut().queue_key("V", false);
+    harness.run(1).unwrap();
+
+    assert!(!harness.machine().keypad()[0xF]);
+
+    // the rom draws the F at (28, 13) after the wait
+    harness.run(2).unwrap();
+
+    let rows: Vec<String> = harness.ascii().lines().map(|row| row[28..32].to_string()).collect();
+
+    assert_eq!(rows[13..18], ["####", "#...", "####", "#...", "#..."]);
+}
+
+
+#[test]
+fn sources_hold_keys_independently() {
+    let mut harness: Harness = Harness::new(&assemble_fixture("keys.8o")).unwrap();
+    let pad: VirtualController = VirtualController::new();
+
+    harness.machine_mut().add_input_source(Box::new(pad.clone()));
+
+    // W and the south button are both bound to 5
+    harness.machine_mut().queue_key("W", true);
+    pad.press(PadButton::South);
+    harness.run(1).unwrap();
+
+    pad.release(PadButton::South);
+    harness.run(1).unwrap();
+    assert!(harness.machine().keypad()[0x5]);
+
+    pad.press(PadButton::South);
+    harness.machine_mut().queue_key("W", false);
+    harness.run(1).unwrap();
+    assert!(harness.machine().keypad()[0x5]);
+
+    pad.release(PadButton::South);
+    harness.run(1).unwrap();
+    assert!(!harness.machine().keypad()[0x5]);
+}
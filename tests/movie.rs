@@ -0,0 +1,97 @@
+mod common;
+
+use chip_8_emu::{
+    Machine,
+    harness,
+    movie::Movie,
+    rewind::Rewind
+};
+use common::assemble_fixture;
+
+
+// taps `key` for two frames, then runs until `frames` have passed
+fn tap(machine: &mut Machine, key: usize, frames: u64) {
+    for frame in 0..frames {
+        machine.set_key(key, frame < 2);
+        machine.step_frame().unwrap();
+    }
+}
+
+
+#[test]
+fn movie_survives_loading_states_and_rewinding() {
+    let rom: Vec<u8> = assemble_fixture("keys.8o");
+    let mut machine: Machine = Machine::new();
+
+    machine.set_seed(0);
+    machine.set_rewind(Some(Rewind::default()));
+    machine.init_from_bytes(&rom).unwrap();
+    machine.start_recording();
+
+    tap(&mut machine, 0xA, 6);
+
+    let state: Vec<u8> = machine.save_state();
+
+    // a branch that is thrown away by loading the state
+    tap(&mut machine, 0x3, 6);
+    machine.load_state(&state).unwrap();
+
+    // and one thrown away by rewinding
+    tap(&mut machine, 0xC, 3);
+
+    for _ in 0..3 {
+        assert!(machine.rewind_frame().unwrap());
+    }
+
+    tap(&mut machine, 0x5, 6);
+
+    let ascii: String = harness::to_ascii(machine.display());
+    let recorded: Movie = machine.stop_recording().unwrap();
+    let movie: Movie = recorded.to_string().parse().unwrap();
+
+    assert_eq!(movie, recorded);
+    assert_eq!(movie.frames.len() as u64, machine.frame());
+
+    let replayed: Machine = movie.replay(&rom).unwrap();
+
+    assert_eq!(harness::to_ascii(replayed.display()), ascii);
+}
+
+
+#[test]
+fn states_past_the_recording_are_refused() {
+    let rom: Vec<u8> = assemble_fixture("keys.8o");
+    let mut machine: Machine = Machine::new();
+
+    machine.init_from_bytes(&rom).unwrap();
+    tap(&mut machine, 0x1, 4);
+
+    let state: Vec<u8> = machine.save_state();
+    let mut recording: Machine = Machine::new();
+
+    recording.init_from_bytes(&rom).unwrap();
+    recording.start_recording();
+    tap(&mut recording, 0x2, 2);
+
+    assert!(recording.load_state(&state).is_err());
+    assert_eq!(recording.frame(), 2);
+}
+
+
+#[test]
+fn memory_sizes_beyond_64k_are_refused() {
+    let rom: Vec<u8> = assemble_fixture("keys.8o");
+    let mut machine: Machine = Machine::new();
+
+    machine.init_from_bytes(&rom).unwrap();
+    machine.start_recording();
+    tap(&mut machine, 0x4, 2);
+
+    let text: String = machine.stop_recording().unwrap().to_string();
+
+    for (size, valid) in [("65536", true), ("65537", false), ("18446744073709551615", false)] {
+        let edited: String = text.replace("memory 4096", &format!("memory {}", size));
+
+        assert_eq!(edited.parse::<Movie>().is_ok(), valid, "memory {}", size);
+    }
+}
@@ -0,0 +1,191 @@
+use chip_8_emu::{
+    Machine,
+    asm::{
+        self,
+        Assembly
+    },
+    cpu::quirks::Quirks
+};
+
+
+fn machine(quirks: Quirks, source: &str) -> (Machine, Assembly) {
+    let assembly: Assembly = asm::assemble(source).unwrap();
+    let mut machine: Machine = Machine::with_quirks(quirks);
+
+    machine.init_from_bytes(&assembly.rom).unwrap();
+    (machine, assembly)
+}
+
+
+// runs `source` for one frame with a single quirk changed from the VIP
+fn run(quirk: &str, value: &str, source: &str) -> Machine {
+    let mut quirks: Quirks = Quirks::cosmac_vip();
+
+    quirks.set(quirk, value).unwrap();
+
+    let (mut machine, _) = machine(quirks, source);
+
+    machine.step_frame().unwrap();
+    machine
+}
+
+
+#[test]
+fn shift_uses_vy_unless_shifting_in_place() {
+    let source: &str = "
+        : main
+          v0 := 0x10
+          v1 := 0x81
+          v0 >>= v1
+        : spin
+          jump spin
+    ";
+
+    assert_eq!(run("shift", "0", source).cpu().v()[0..2], [0x40, 0x81]);
+    assert_eq!(run("shift", "0", source).cpu().v()[0xF], 1);
+    assert_eq!(run("shift", "1", source).cpu().v()[0..2], [0x08, 0x81]);
+    assert_eq!(run("shift", "1", source).cpu().v()[0xF], 0);
+}
+
+
+#[test]
+fn load_and_store_move_i_by_the_memory_increment() {
+    let source: &str = "
+        : main
+          i := 0x300
+          save v2
+          i := 0x310
+          load v2
+        : spin
+          jump spin
+    ";
+
+    for (value, i) in [("unchanged", 0x310), ("x", 0x312), ("x+1", 0x313)] {
+        assert_eq!(run("memory-increment", value, source).cpu().i(), i, "memory-increment={}", value);
+    }
+}
+
+
+#[test]
+fn jump0_adds_v0_or_vx() {
+    let source: &str = "
+        : main
+          v0 := 2
+          v3 := 4
+          jump0 0x300
+    ";
+    let at = |value: &str| {
+        let mut quirks: Quirks = Quirks::cosmac_vip();
+
+        quirks.set("jump-with-vx", value).unwrap();
+
+        let (mut machine, _) = machine(quirks, source);
+
+        for _ in 0..3 {
+            machine.step_instruction().unwrap();
+        }
+
+        machine.cpu().pc()
+    };
+
+    assert_eq!(at("0"), 0x302);
+    assert_eq!(at("1"), 0x304);
+}
+
+
+#[test]
+fn logic_ops_reset_vf() {
+    let source: &str = "
+        : main
+          vf := 5
+          v0 |= v1
+          v2 := 5
+          v3 := 6
+          vf := 5
+          v2 &= v3
+        : spin
+          jump spin
+    ";
+
+    assert_eq!(run("vf-reset", "1", source).cpu().v()[0xF], 0);
+    assert_eq!(run("vf-reset", "0", source).cpu().v()[0xF], 5);
+    assert_eq!(run("vf-reset", "0", source).cpu().v()[2], 4);
+}
+
+
+#[test]
+fn sprites_clip_or_wrap_at_the_edges() {
+    let source: &str = "
+        : main
+          i := line
+          v0 := 60
+          v1 := 31
+          sprite v0 v1 2
+        : spin
+          jump spin
+        : line
+          0xFF 0xFF
+    ";
+    // x of the lit pixels on the first and last row
+    let lit = |machine: &Machine, row: usize| -> Vec<usize> {
+        let width: usize = machine.display().width();
+
+        (0..width).filter(|x| machine.display().pixels()[row * width + x] != 0).collect()
+    };
+
+    let clipped: Machine = run("wrap", "0", source);
+
+    assert_eq!(lit(&clipped, 31), [60, 61, 62, 63]);
+    assert!(lit(&clipped, 0).is_empty());
+
+    let wrapped: Machine = run("wrap", "1", source);
+
+    assert_eq!(lit(&wrapped, 31), [0, 1, 2, 3, 60, 61, 62, 63]);
+    assert_eq!(lit(&wrapped, 0), [0, 1, 2, 3, 60, 61, 62, 63]);
+}
+
+
+#[test]
+fn display_wait_ends_the_frame_after_a_sprite() {
+    let source: &str = "
+        : main
+          sprite v0 v0 1
+        : second
+          sprite v0 v0 1
+        : third
+          v0 := 1
+        : spin
+          jump spin
+    ";
+    let after = |value: &str| {
+        let mut quirks: Quirks = Quirks::cosmac_vip();
+
+        quirks.set("display-wait", value).unwrap();
+
+        let (mut machine, assembly) = machine(quirks, source);
+
+        machine.step_frame().unwrap();
+        (machine.cpu().pc(), assembly)
+    };
+
+    let (pc, assembly) = after("1");
+
+    assert_eq!(Some(pc), assembly.symbols.get("second"));
+
+    let (pc, assembly) = after("0");
+
+    assert_eq!(Some(pc), assembly.symbols.get("spin"));
+}
+
+
+#[test]
+fn stack_depth_must_fit_the_stack() {
+    let mut quirks: Quirks = Quirks::cosmac_vip();
+
+    assert!(quirks.set("stack-depth", "0").is_err());
+    assert!(quirks.set("stack-depth", "256").is_err());
+    assert_eq!(quirks.stack_depth, 12);
+
+    quirks.set("stack-depth", "255").unwrap();
+    assert_eq!(quirks.stack_depth, 255);
+}
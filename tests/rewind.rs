@@ -0,0 +1,65 @@
+mod common;
+
+use chip_8_emu::{
+    Machine,
+    harness::Harness,
+    rewind::Rewind
+};
+use common::assemble_fixture;
+
+
+fn font_harness(rewind: Rewind) -> Harness {
+    let mut machine: Machine = Machine::new();
+
+    machine.set_seed(0);
+    machine.set_rewind(Some(rewind));
+    machine.init_from_bytes(&assemble_fixture("font.8o")).unwrap();
+
+    Harness::from_machine(machine)
+}
+
+
+#[test]
+fn rewinding_restores_earlier_frames() {
+    let mut harness: Harness = font_harness(Rewind::default());
+
+    harness.run(5).unwrap();
+
+    let state: Vec<u8> = harness.machine().save_state();
+    let ascii: String = harness.ascii();
+
+    harness.run(12).unwrap();
+    assert_ne!(harness.ascii(), ascii);
+
+    for _ in 0..12 {
+        assert!(harness.machine_mut().rewind_frame().unwrap());
+    }
+
+    assert_eq!(harness.machine().frame(), 5);
+    assert_eq!(harness.machine().save_state(), state);
+    assert_eq!(harness.ascii(), ascii);
+
+    // the history before the rewound frames is still there
+    assert_eq!(harness.machine().rewind().unwrap().len(), 4);
+}
+
+
+#[test]
+fn full_history_drops_the_oldest_frames() {
+    let mut harness: Harness = font_harness(Rewind::new(3));
+
+    harness.run(7).unwrap();
+
+    let oldest: Vec<u8> = harness.machine().save_state();
+
+    harness.run(3).unwrap();
+    assert_eq!(harness.machine().rewind().unwrap().len(), 3);
+
+    for _ in 0..3 {
+        assert!(harness.machine_mut().rewind_frame().unwrap());
+    }
+
+    assert!(!harness.machine_mut().rewind_frame().unwrap());
+    assert_eq!(harness.machine().frame(), 7);
+    assert_eq!(harness.machine().save_state(), oldest);
+}
@@ -0,0 +1,84 @@
+use chip_8_emu::{
+    Machine,
+    asm::{
+        self,
+        Assembly
+    },
+    cpu::rng::{
+        Algorithm,
+        Rng
+    }
+};
+
+
+fn machine(source: &str) -> (Machine, Assembly) {
+    let assembly: Assembly = asm::assemble(source).unwrap();
+    let mut machine: Machine = Machine::new();
+
+    machine.init_from_bytes(&assembly.rom).unwrap();
+    (machine, assembly)
+}
+
+
+#[test]
+fn cxnn_masks_the_scripted_values() {
+    let (mut machine, _) = machine("
+        : main
+          v0 := random 0x0F
+          v1 := random 0xF0
+          v2 := random 0xFF
+          v3 := random 0x3C
+        : spin
+          jump spin
+    ");
+
+    machine.set_rng(Rng::scripted(vec![0xAB, 0xCD, 0x12]));
+    machine.step_frame().unwrap();
+
+    // the script starts over after its last value
+    assert_eq!(machine.cpu().v()[..4], [0x0B, 0xC0, 0x12, 0xAB & 0x3C]);
+}
+
+
+#[test]
+fn vip_generator_adds_interpreter_bytes_and_the_frame() {
+    // seed 0x0102 starts at value 2 with the pointer on the second font byte
+    let mut rng: Rng = Rng::new(Algorithm::CosmacVip, 0x0102);
+
+    assert_eq!(rng.next_u8(0), 0x92);
+    assert_eq!(rng.next_u8(5), 0x27);
+    assert_eq!(rng.next_u8(0xFF), 0xB6);
+}
+
+
+#[test]
+fn vip_generator_sees_the_machine_frame() {
+    // with display wait every sprite ends a frame long before the instruction budget
+    let (mut machine, assembly) = machine("
+        : main
+          i := dot
+          sprite v0 v0 1
+          sprite v0 v0 1
+          sprite v0 v0 1
+        : roll
+          v1 := random 0xFF
+        : spin
+          jump spin
+        : dot
+          0x80
+    ");
+    let roll: u16 = assembly.symbols.get("roll").unwrap();
+
+    machine.set_seed(7);
+    machine.set_rng_algorithm(Algorithm::CosmacVip);
+
+    while machine.cpu().pc() != roll {
+        machine.step_instruction().unwrap();
+    }
+
+    assert_eq!(machine.frame(), 3);
+
+    machine.step_instruction().unwrap();
+
+    assert_eq!(machine.cpu().v()[1], Rng::new(Algorithm::CosmacVip, 7).next_u8(3));
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use chip_8_emu::{
+    Chip8Error,
+    Machine,
+    harness::Harness,
+    savestate
+};
+use common::assemble_fixture;
+
+
+// a machine part way through drawing the font
+fn harness() -> Harness {
+    let mut harness: Harness = Harness::new(&assemble_fixture("font.8o")).unwrap();
+
+    harness.press(0, 0x7).run(6).unwrap();
+    harness
+}
+
+
+fn is_bad_state(result: Result<(), Chip8Error>) -> bool {
+    matches!(result, Err(Chip8Error::BadSaveState { .. }))
+}
+
+
+#[test]
+fn round_trip() {
+    let mut harness: Harness = harness();
+    let state: Vec<u8> = harness.machine().save_state();
+
+    harness.run(14).unwrap();
+
+    let finished: String = harness.ascii();
+    let mut restored: Harness = Harness::new(&assemble_fixture("font.8o")).unwrap();
+
+    restored.machine_mut().load_state(&state).unwrap();
+
+    assert_eq!(restored.machine().save_state(), state);
+    assert_eq!(restored.machine().frame(), 6);
+    assert!(restored.machine().keypad()[0x7]);
+
+    restored.run(14).unwrap();
+
+    assert_eq!(restored.ascii(), finished);
+    assert_eq!(restored.machine().save_state(), harness.machine().save_state());
+}
+
+
+#[test]
+fn unknown_chunks_are_skipped() {
+    let harness: Harness = harness();
+    let mut state: Vec<u8> = harness.machine().save_state();
+
+    // a chunk of a newer version
+    state.extend_from_slice(b"NEW!");
+    state.extend_from_slice(&3u32.to_le_bytes());
+    state.extend_from_slice(&[1, 2, 3]);
+
+    let mut machine: Machine = Machine::new();
+
+    machine.load_state(&state).unwrap();
+    assert_eq!(machine.save_state(), harness.machine().save_state());
+}
+
+
+#[test]
+fn truncated_state_is_rejected() {
+    let state: Vec<u8> = harness().machine().save_state();
+    let mut machine: Machine = Machine::new();
+    let untouched: Vec<u8> = machine.save_state();
+
+    for len in [0, 5, 6 + 3, state.len() / 2, state.len() - 1] {
+        assert!(is_bad_state(machine.load_state(&state[..len])), "loaded {} of {} bytes", len, state.len());
+    }
+
+    assert_eq!(machine.save_state(), untouched);
+}
+
+
+#[test]
+fn newer_version_is_rejected() {
+    let mut state: Vec<u8> = harness().machine().save_state();
+    let mut machine: Machine = Machine::new();
+
+    state[4..6].copy_from_slice(&(savestate::VERSION + 1).to_le_bytes());
+
+    assert!(is_bad_state(machine.load_state(&state)));
+}
+
+
+#[test]
+fn audio_position_outside_the_pattern_is_rejected() {
+    let state: Vec<u8> = harness().machine().save_state();
+    // tag, chunk length, the 16 byte pattern with its length and the pitch come first
+    let audio: usize = state.windows(4).position(|tag| tag == b"AUDI").unwrap();
+    let position: usize = audio + 4 + 4 + 4 + 16 + 1;
+    let mut machine: Machine = Machine::new();
+
+    for bad in [1e9, 128.0, -1.0, f64::NAN, f64::INFINITY] {
+        let mut corrupt: Vec<u8> = state.clone();
+
+        corrupt[position..position + 8].copy_from_slice(&f64::to_le_bytes(bad));
+        assert!(is_bad_state(machine.load_state(&corrupt)), "loaded position {}", bad);
+    }
+
+    // the offset points at the position, an in range value loads
+    let mut moved: Vec<u8> = state.clone();
+
+    moved[position..position + 8].copy_from_slice(&f64::to_le_bytes(127.5));
+    machine.load_state(&moved).unwrap();
+}